@@ -0,0 +1,332 @@
+use std::{
+  collections::VecDeque,
+  io::{self, Write},
+  mem,
+  str::FromStr,
+  sync::{
+    atomic::{AtomicU64, Ordering},
+    mpsc, Arc, Condvar, Mutex, MutexGuard,
+  },
+  thread::{self, JoinHandle},
+};
+
+use crate::error::{Error, Result};
+
+/// The name of the writer threads.
+pub(crate) const THREAD_NAME: &str = "yaslog-writer";
+
+/// What to do with a record when the queue of an asynchronous output is full.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+  /// Wait for the writer thread to make room.
+  #[default]
+  Block,
+  /// Drop the record being logged.
+  DropNewest,
+  /// Drop the oldest record of the queue to make room, or the record being
+  /// logged if the queued ones are all being written already.
+  DropOldest,
+}
+
+impl FromStr for OverflowPolicy {
+  type Err = String;
+
+  /// Parses `block`, `drop_newest` or `drop_oldest`, ignoring case.
+  fn from_str(overflow: &str) -> std::result::Result<Self, String> {
+    match overflow.to_ascii_lowercase().as_str() {
+      "block" => Ok(OverflowPolicy::Block),
+      "drop_newest" => Ok(OverflowPolicy::DropNewest),
+      "drop_oldest" => Ok(OverflowPolicy::DropOldest),
+      _ => Err(format!("unknown overflow policy `{}`", overflow)),
+    }
+  }
+}
+
+/// Options of the queues of asynchronous outputs.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct QueueOptions {
+  pub(crate) capacity: usize,
+  pub(crate) overflow: OverflowPolicy,
+}
+
+enum Message {
+  Record(Vec<u8>),
+  /// Asks the writer thread to flush the output and to answer once done.
+  Flush(mpsc::Sender<()>),
+}
+
+struct Queue {
+  state: Mutex<State>,
+  not_empty: Condvar,
+  not_full: Condvar,
+  options: QueueOptions,
+  dropped: Arc<AtomicU64>,
+}
+
+struct State {
+  messages: VecDeque<Message>,
+  /// The records queued or being written.
+  records: usize,
+  closed: bool,
+}
+
+/// An output which collects the bytes of a record and hands them to a writer
+/// thread on `flush`, which fern calls after every record. A flush with no
+/// record pending waits for the queue to be written and flushed instead.
+pub(crate) struct AsyncWriter {
+  buffer: Vec<u8>,
+  queue: Arc<Queue>,
+  thread: Option<JoinHandle<()>>,
+}
+
+impl AsyncWriter {
+  /// Starts the thread writing to `output`, counting the dropped records in
+  /// `dropped`.
+  pub(crate) fn spawn(
+    mut output: Box<dyn Write + Send>, options: QueueOptions, dropped: Arc<AtomicU64>,
+  ) -> Result<Self> {
+    let queue = Arc::new(Queue {
+      state: Mutex::new(State { messages: VecDeque::new(), records: 0, closed: false }),
+      not_empty: Condvar::new(),
+      not_full: Condvar::new(),
+      options,
+      dropped,
+    });
+    let thread = {
+      let queue = Arc::clone(&queue);
+      thread::Builder::new()
+        .name(THREAD_NAME.to_string())
+        .spawn(move || {
+          while let Some(messages) = queue.take() {
+            let mut written = 0;
+            for message in messages {
+              let result = match message {
+                Message::Record(record) => {
+                  written += 1;
+                  output.write_all(&record).and_then(|_| output.flush())
+                }
+                Message::Flush(done) => {
+                  let result = output.flush();
+                  let _ = done.send(());
+                  result
+                }
+              };
+              if let Err(err) = result {
+                eprintln!("yaslog: failed to write a record: {}", err);
+              }
+            }
+            queue.written(written);
+          }
+        })
+        .map_err(Error::Spawn)?
+    };
+    Ok(Self { buffer: Vec::new(), queue, thread: Some(thread) })
+  }
+}
+
+impl Queue {
+  fn lock(&self) -> MutexGuard<'_, State> {
+    self.state.lock().unwrap_or_else(|err| err.into_inner())
+  }
+
+  fn push_record(&self, record: Vec<u8>) {
+    let mut state = self.lock();
+    while state.records >= self.options.capacity.max(1) {
+      match self.options.overflow {
+        OverflowPolicy::Block => {
+          state = self.not_full.wait(state).unwrap_or_else(|err| err.into_inner());
+        }
+        OverflowPolicy::DropNewest => {
+          self.dropped.fetch_add(1, Ordering::Relaxed);
+          return;
+        }
+        OverflowPolicy::DropOldest => {
+          let oldest =
+            state.messages.iter().position(|message| matches!(message, Message::Record(_)));
+          self.dropped.fetch_add(1, Ordering::Relaxed);
+          match oldest {
+            Some(oldest) => {
+              state.messages.remove(oldest);
+              state.records -= 1;
+            }
+            None => return,
+          }
+        }
+      }
+    }
+    state.messages.push_back(Message::Record(record));
+    state.records += 1;
+    self.not_empty.notify_one();
+  }
+
+  /// Queues a flush, which is never dropped, and returns the receiver of its
+  /// answer.
+  fn push_flush(&self) -> mpsc::Receiver<()> {
+    let (done, receiver) = mpsc::channel();
+    self.lock().messages.push_back(Message::Flush(done));
+    self.not_empty.notify_one();
+    receiver
+  }
+
+  /// Waits for messages and takes all of them, or returns `None` once the
+  /// queue is closed and empty. Their records still count until `written`.
+  fn take(&self) -> Option<VecDeque<Message>> {
+    let mut state = self.lock();
+    while state.messages.is_empty() {
+      if state.closed {
+        return None;
+      }
+      state = self.not_empty.wait(state).unwrap_or_else(|err| err.into_inner());
+    }
+    Some(mem::take(&mut state.messages))
+  }
+
+  /// Makes room for the `records` written from the messages taken.
+  fn written(&self, records: usize) {
+    if records > 0 {
+      self.lock().records -= records;
+      self.not_full.notify_all();
+    }
+  }
+
+  fn close(&self) {
+    self.lock().closed = true;
+    self.not_empty.notify_one();
+  }
+}
+
+impl Write for AsyncWriter {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.buffer.extend_from_slice(buf);
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    if self.buffer.is_empty() {
+      let _ = self.queue.push_flush().recv();
+    } else {
+      self.queue.push_record(mem::take(&mut self.buffer));
+    }
+    Ok(())
+  }
+}
+
+impl Drop for AsyncWriter {
+  /// Writes the queued records before returning.
+  fn drop(&mut self) {
+    self.queue.close();
+    if let Some(thread) = self.thread.take() {
+      let _ = thread.join();
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{thread, time::Duration};
+
+  use super::*;
+
+  /// An output holding every write until the test drops the gate's sender.
+  struct Held {
+    gate: mpsc::Receiver<()>,
+    written: Arc<Mutex<Vec<String>>>,
+  }
+
+  impl Write for Held {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+      let _ = self.gate.recv();
+      self.written.lock().unwrap().push(String::from_utf8_lossy(buf).into_owned());
+      Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+      Ok(())
+    }
+  }
+
+  struct Setup {
+    writer: AsyncWriter,
+    gate: mpsc::Sender<()>,
+    written: Arc<Mutex<Vec<String>>>,
+    dropped: Arc<AtomicU64>,
+  }
+
+  fn setup(capacity: usize, overflow: OverflowPolicy) -> Setup {
+    let (gate, held) = mpsc::channel();
+    let written = Arc::new(Mutex::new(Vec::new()));
+    let dropped = Arc::new(AtomicU64::new(0));
+    let output = Box::new(Held { gate: held, written: Arc::clone(&written) });
+    let options = QueueOptions { capacity, overflow };
+    let writer = AsyncWriter::spawn(output, options, Arc::clone(&dropped)).unwrap();
+    Setup { writer, gate, written, dropped }
+  }
+
+  fn log(writer: &mut AsyncWriter, record: &str) {
+    writer.write_all(record.as_bytes()).unwrap();
+    writer.flush().unwrap();
+  }
+
+  /// Logs `record` and waits for the writer thread to take it.
+  fn log_taken(writer: &mut AsyncWriter, record: &str) {
+    log(writer, record);
+    while !writer.queue.lock().messages.is_empty() {
+      thread::sleep(Duration::from_millis(1));
+    }
+  }
+
+  /// Opens the gate, waits for the records to be written and returns them.
+  fn finish(setup: Setup) -> (Vec<String>, u64) {
+    drop(setup.gate);
+    drop(setup.writer);
+    let written = setup.written.lock().unwrap().clone();
+    (written, setup.dropped.load(Ordering::Relaxed))
+  }
+
+  #[test]
+  fn drops_the_newest_records() {
+    let mut setup = setup(2, OverflowPolicy::DropNewest);
+    log_taken(&mut setup.writer, "1");
+    for record in ["2", "3", "4"] {
+      log(&mut setup.writer, record);
+    }
+    assert_eq!(finish(setup), (vec!["1".to_string(), "2".to_string()], 2));
+  }
+
+  #[test]
+  fn drops_the_oldest_records() {
+    let mut setup = setup(3, OverflowPolicy::DropOldest);
+    log_taken(&mut setup.writer, "1");
+    for record in ["2", "3", "4", "5"] {
+      log(&mut setup.writer, record);
+    }
+    let expected = ["1", "4", "5"].map(String::from).to_vec();
+    assert_eq!(finish(setup), (expected, 2));
+  }
+
+  #[test]
+  fn drops_the_newest_records_once_all_are_being_written() {
+    let mut setup = setup(1, OverflowPolicy::DropOldest);
+    log_taken(&mut setup.writer, "1");
+    log(&mut setup.writer, "2");
+    assert_eq!(finish(setup), (vec!["1".to_string()], 1));
+  }
+
+  #[test]
+  fn blocks_until_there_is_room() {
+    let mut setup = setup(1, OverflowPolicy::Block);
+    log_taken(&mut setup.writer, "1");
+    let queue = Arc::clone(&setup.writer.queue);
+    let (pushed, done) = mpsc::channel();
+    let blocked = thread::spawn(move || {
+      queue.push_record(b"2".to_vec());
+      let _ = pushed.send(());
+    });
+    assert!(done.recv_timeout(Duration::from_millis(50)).is_err());
+    // Replacing the gate's sender opens it.
+    setup.gate = mpsc::channel().0;
+    done.recv().unwrap();
+    blocked.join().unwrap();
+    assert_eq!(finish(setup), (vec!["1".to_string(), "2".to_string()], 0));
+  }
+}
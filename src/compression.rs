@@ -0,0 +1,80 @@
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+use std::{
+  ffi::OsString,
+  fs::{self, File},
+  io::{BufReader, BufWriter, Write},
+};
+use std::{
+  io,
+  path::{Path, PathBuf},
+  str::FromStr,
+};
+
+/// Extensions of every compression format, compiled in or not, so rotated
+/// files written by other builds are still recognized.
+pub(crate) const COMPRESSED_EXTENSIONS: [&str; 2] = ["gz", "zst"];
+
+/// Formats rotated log files can be compressed with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+  /// Compress to `<name>.gz`.
+  #[cfg(feature = "gzip")]
+  Gzip,
+  /// Compress to `<name>.zst`.
+  #[cfg(feature = "zstd")]
+  Zstd,
+}
+
+impl Compression {
+  /// Compresses the file at `path` next to it and removes the original.
+  #[cfg_attr(not(any(feature = "gzip", feature = "zstd")), allow(unused_variables))]
+  pub(crate) fn compress(self, path: &Path) -> io::Result<PathBuf> {
+    match self {
+      #[cfg(feature = "gzip")]
+      Compression::Gzip => Self::encode(path, "gz", |reader, writer| {
+        let mut encoder = flate2::write::GzEncoder::new(writer, flate2::Compression::default());
+        io::copy(reader, &mut encoder)?;
+        encoder.finish()
+      }),
+      #[cfg(feature = "zstd")]
+      Compression::Zstd => Self::encode(path, "zst", |reader, writer| {
+        let mut encoder = zstd::Encoder::new(writer, 0)?;
+        io::copy(reader, &mut encoder)?;
+        encoder.finish()
+      }),
+    }
+  }
+
+  #[cfg(any(feature = "gzip", feature = "zstd"))]
+  fn encode<F>(path: &Path, extension: &str, encode: F) -> io::Result<PathBuf>
+  where
+    F: FnOnce(&mut BufReader<File>, BufWriter<File>) -> io::Result<BufWriter<File>>,
+  {
+    let mut compressed_path = OsString::from(path);
+    compressed_path.push(".");
+    compressed_path.push(extension);
+    let compressed_path = PathBuf::from(compressed_path);
+
+    let mut reader = BufReader::new(File::open(path)?);
+    let writer = BufWriter::new(File::create(&compressed_path)?);
+    encode(&mut reader, writer)?.flush()?;
+
+    fs::remove_file(path)?;
+    Ok(compressed_path)
+  }
+}
+
+impl FromStr for Compression {
+  type Err = String;
+
+  /// Parses `gzip` or `zstd`, ignoring case, if compiled in.
+  fn from_str(compression: &str) -> Result<Self, String> {
+    match compression.to_ascii_lowercase().as_str() {
+      #[cfg(feature = "gzip")]
+      "gzip" => Ok(Compression::Gzip),
+      #[cfg(feature = "zstd")]
+      "zstd" => Ok(Compression::Zstd),
+      _ => Err(format!("unknown or disabled compression `{}`", compression)),
+    }
+  }
+}
@@ -0,0 +1,527 @@
+use std::{
+  fmt::Display,
+  fs,
+  path::{Path, PathBuf},
+  result::Result as StdResult,
+  str::FromStr,
+  sync::Arc,
+  thread,
+  time::{Duration, SystemTime},
+};
+
+use log::LevelFilter;
+use serde::{de, Deserialize, Deserializer};
+
+use crate::{
+  async_writer::{OverflowPolicy, QueueOptions},
+  compression::Compression,
+  format::LogFormat,
+  logger::{Error, LogLevel, LogTarget, Logger, LoggerBuilder, Result, Target},
+  naming::FileNaming,
+  rotating_file::{RotationClock, RotationPeriod, RotationPolicy},
+  syslog::{Facility, Syslog, SyslogProtocol},
+  units,
+};
+
+/// Languages of configuration files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigFormat {
+  Toml,
+  Yaml,
+  Json,
+}
+
+impl ConfigFormat {
+  /// Guesses the language of the file at `path` from its extension.
+  pub fn from_path(path: &Path) -> Option<Self> {
+    match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+      "toml" => Some(ConfigFormat::Toml),
+      "yaml" | "yml" => Some(ConfigFormat::Yaml),
+      "json" => Some(ConfigFormat::Json),
+      _ => None,
+    }
+  }
+}
+
+/// Configuration of a logger, mirroring `LoggerBuilder`, available with the
+/// `config` feature. Unset or null fields keep the builder's defaults.
+///
+/// Levels, formats, rotation periods, clocks and compressions are written as
+/// their names, e.g. `"info"`, `"json"`, `"daily"`, `"utc"` or `"zstd"`,
+/// sizes as bytes or like `"10MB"` and durations as seconds or like `"7d"`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggerConfig {
+  #[serde(deserialize_with = "parsed")]
+  pub level: Option<LevelFilter>,
+  pub directives: Option<String>,
+  #[serde(deserialize_with = "parsed")]
+  pub format: Option<LogFormat>,
+  pub format_template: Option<String>,
+  #[serde(deserialize_with = "parsed")]
+  pub stderr_level: Option<LogLevel>,
+  pub rotation: RotationConfig,
+  pub async_queue: Option<AsyncQueueConfig>,
+  pub log_panics: Option<bool>,
+  pub targets: Vec<TargetConfig>,
+}
+
+/// The queue of asynchronous outputs, see `LoggerBuilder::async_queue`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AsyncQueueConfig {
+  pub capacity: usize,
+  #[serde(default, deserialize_with = "parsed")]
+  pub overflow: Option<OverflowPolicy>,
+}
+
+/// Rotation options of a logger, or of one of its file targets.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RotationConfig {
+  #[serde(deserialize_with = "size")]
+  pub max_file_size: Option<u128>,
+  pub max_rotated_files: Option<usize>,
+  #[serde(deserialize_with = "parsed")]
+  pub period: Option<RotationPeriod>,
+  #[serde(deserialize_with = "parsed")]
+  pub clock: Option<RotationClock>,
+  #[serde(deserialize_with = "parsed")]
+  pub compression: Option<Compression>,
+  #[serde(deserialize_with = "size")]
+  pub max_total_size: Option<u128>,
+  #[serde(deserialize_with = "duration")]
+  pub max_age: Option<Duration>,
+}
+
+/// Kinds of log targets, see `LogTarget`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TargetKind {
+  Console,
+  Stderr,
+  SplitConsole,
+  Dir,
+  File,
+  Syslog,
+}
+
+/// Sockets a syslog target can send its messages through.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyslogTransport {
+  /// A Unix datagram socket, `/dev/log` by default.
+  #[default]
+  Unix,
+  Udp,
+  Tcp,
+}
+
+/// The daemon of a syslog target and its options, see `Syslog`. UDP and TCP
+/// transports need an `address`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SyslogConfig {
+  pub transport: SyslogTransport,
+  /// The socket path or the `host:port` of the daemon.
+  pub address: Option<String>,
+  #[serde(deserialize_with = "parsed")]
+  pub protocol: Option<SyslogProtocol>,
+  #[serde(deserialize_with = "parsed")]
+  pub facility: Option<Facility>,
+  pub app_name: Option<String>,
+  pub hostname: Option<String>,
+}
+
+/// A log target and its own options. Dir and file targets need a `path`,
+/// and may override the file names and the logger's rotation options. Syslog
+/// targets may have a `syslog` table. Any target may have its own level and
+/// directives.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TargetConfig {
+  pub kind: TargetKind,
+  #[serde(default)]
+  pub path: Option<PathBuf>,
+  #[serde(default, deserialize_with = "parsed")]
+  pub format: Option<LogFormat>,
+  /// The level of this target, see `Target::level`.
+  #[serde(default, deserialize_with = "parsed")]
+  pub level: Option<LevelFilter>,
+  /// The directives of this target, see `Target::directives`.
+  #[serde(default)]
+  pub directives: Option<String>,
+  /// The base name of the files of a dir target.
+  #[serde(default)]
+  pub name: Option<String>,
+  /// The extension of the files of a dir target.
+  #[serde(default)]
+  pub extension: Option<String>,
+  /// The template of the rotated file names, see `FileNaming`.
+  #[serde(default)]
+  pub rotated: Option<String>,
+  #[serde(default)]
+  pub rotation: Option<RotationConfig>,
+  #[serde(default)]
+  pub syslog: Option<SyslogConfig>,
+}
+
+impl LoggerConfig {
+  /// Reads the configuration from the file at `path`, in the language given
+  /// by its extension.
+  pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+    let path = path.as_ref();
+    let format = ConfigFormat::from_path(path).ok_or_else(|| {
+      Error::InvalidConfig(format!("unknown config language of {}", path.display()))
+    })?;
+    let text = fs::read_to_string(path)
+      .map_err(|source| Error::ReadConfig { path: path.to_path_buf(), source })?;
+    Self::parse(&text, format)
+  }
+
+  /// Parses the configuration from `text` written in `format`.
+  pub fn parse(text: &str, format: ConfigFormat) -> Result<Self> {
+    let invalid = |err: &dyn Display| Error::InvalidConfig(err.to_string());
+    match format {
+      ConfigFormat::Toml => toml::from_str(text).map_err(|err| invalid(&err)),
+      ConfigFormat::Yaml => serde_yaml::from_str(text).map_err(|err| invalid(&err)),
+      ConfigFormat::Json => serde_json::from_str(text).map_err(|err| invalid(&err)),
+    }
+  }
+}
+
+impl RotationConfig {
+  fn apply(&self, rotation: &mut RotationPolicy) {
+    if let Some(max_file_size) = self.max_file_size {
+      rotation.max_file_size = max_file_size;
+    }
+    if let Some(max_rotated_files) = self.max_rotated_files {
+      rotation.max_rotated_files = max_rotated_files;
+    }
+    if self.period.is_some() {
+      rotation.period = self.period;
+    }
+    if let Some(clock) = self.clock {
+      rotation.clock = clock;
+    }
+    if self.compression.is_some() {
+      rotation.compression = self.compression;
+    }
+    if self.max_total_size.is_some() {
+      rotation.max_total_size = self.max_total_size;
+    }
+    if self.max_age.is_some() {
+      rotation.max_age = self.max_age;
+    }
+  }
+}
+
+impl TargetConfig {
+  fn target(&self, rotation: &RotationPolicy) -> Result<Target> {
+    let path = || {
+      let message = || format!("{:?} target without a path", self.kind);
+      self.path.clone().ok_or_else(|| Error::InvalidConfig(message()))
+    };
+    let mut target = Target::from(match self.kind {
+      TargetKind::Console => LogTarget::Console,
+      TargetKind::Stderr => LogTarget::Stderr,
+      TargetKind::SplitConsole => LogTarget::SplitConsole,
+      TargetKind::Dir => LogTarget::Dir(path()?),
+      TargetKind::File => LogTarget::File(path()?),
+      TargetKind::Syslog => LogTarget::Syslog(self.syslog.clone().unwrap_or_default().syslog()?),
+    });
+
+    let mut naming = FileNaming::new(self.name.as_deref().unwrap_or("app"));
+    if let Some(extension) = &self.extension {
+      naming = naming.extension(extension.as_str());
+    }
+    if let Some(rotated) = &self.rotated {
+      naming = naming.rotated(rotated.as_str());
+    }
+    target = target.naming(naming);
+    if let Some(format) = self.format {
+      target = target.format(format);
+    }
+    target.level = self.level;
+    target.directives = self.directives.clone();
+    if let Some(config) = &self.rotation {
+      let mut rotation = rotation.clone();
+      config.apply(&mut rotation);
+      target.rotation = Some(rotation);
+    }
+    Ok(target)
+  }
+}
+
+impl SyslogConfig {
+  fn syslog(&self) -> Result<Syslog> {
+    let address = || {
+      let message = || format!("{:?} syslog target without an address", self.transport);
+      self.address.clone().ok_or_else(|| Error::InvalidConfig(message()))
+    };
+    let mut syslog = match self.transport {
+      #[cfg(unix)]
+      SyslogTransport::Unix => match &self.address {
+        Some(path) => Syslog::unix(path),
+        None => Syslog::local(),
+      },
+      #[cfg(not(unix))]
+      SyslogTransport::Unix => {
+        return Err(Error::InvalidConfig("Unix syslog sockets are unsupported".to_string()))
+      }
+      SyslogTransport::Udp => Syslog::udp(address()?),
+      SyslogTransport::Tcp => Syslog::tcp(address()?),
+    };
+    if let Some(protocol) = self.protocol {
+      syslog = syslog.protocol(protocol);
+    }
+    if let Some(facility) = self.facility {
+      syslog = syslog.facility(facility);
+    }
+    if let Some(app_name) = &self.app_name {
+      syslog = syslog.app_name(app_name.as_str());
+    }
+    if let Some(hostname) = &self.hostname {
+      syslog = syslog.hostname(hostname.as_str());
+    }
+    Ok(syslog)
+  }
+}
+
+impl LoggerBuilder {
+  /// Returns a builder configured by `config`. Builder calls made afterwards
+  /// override it.
+  pub fn from_config(config: &LoggerConfig) -> Result<Self> {
+    let mut builder = Self::new();
+    if let Some(level) = config.level {
+      builder.level = level;
+    }
+    if let Some(directives) = &config.directives {
+      builder.directives = directives.clone();
+    }
+    if let Some(format) = config.format {
+      builder.format = format;
+    }
+    if let Some(format_template) = &config.format_template {
+      builder.format_template = format_template.clone();
+    }
+    if let Some(stderr_level) = config.stderr_level {
+      builder.stderr_level = stderr_level;
+    }
+    config.rotation.apply(&mut builder.rotation);
+    if let Some(log_panics) = config.log_panics {
+      builder.log_panics = log_panics;
+    }
+    if let Some(queue) = &config.async_queue {
+      let overflow = queue.overflow.unwrap_or_default();
+      builder.queue = Some(QueueOptions { capacity: queue.capacity, overflow });
+    }
+    builder.targets = config
+      .targets
+      .iter()
+      .map(|target| target.target(&builder.rotation))
+      .collect::<Result<_>>()?;
+    Ok(builder)
+  }
+
+  /// Returns a builder configured by the file at `path`, see
+  /// `LoggerConfig::from_file`.
+  pub fn from_config_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+    Self::from_config(&LoggerConfig::from_file(path)?)
+  }
+
+  /// Returns a builder configured by `text` written in `format`.
+  pub fn from_config_str(text: &str, format: ConfigFormat) -> Result<Self> {
+    Self::from_config(&LoggerConfig::parse(text, format)?)
+  }
+}
+
+impl Logger {
+  /// Checks the config file at `path` every `interval` on a background thread
+  /// and reloads the logger from it when it changes, see `reload`. Every
+  /// reload is logged, and so is a config which fails to build, the current
+  /// one being kept then. Builder calls made on top of the config are lost.
+  /// The thread stops once the logger is shut down or dropped.
+  pub fn watch_config_file<P: Into<PathBuf>>(&self, path: P, interval: Duration) -> Result<()> {
+    let path = path.into();
+    let shared = Arc::downgrade(&self.shared);
+    let mut loaded = version(&path);
+    thread::Builder::new()
+      .name("yaslog-config".to_string())
+      .spawn(move || loop {
+        thread::sleep(interval);
+        let Some(shared) = shared.upgrade().filter(|shared| !shared.is_shut_down()) else {
+          return;
+        };
+        let current = version(&path);
+        if current == loaded || current.is_none() {
+          continue;
+        }
+        loaded = current;
+        let logger = Logger::new(shared);
+        match LoggerBuilder::from_config_file(&path).and_then(|builder| logger.reload(builder)) {
+          Ok(()) => {
+            log::info!(target: "yaslog", "reloaded the logger config from {}", path.display())
+          }
+          Err(err) => log::error!(
+            target: "yaslog",
+            "kept the logger config, {} failed to load: {}",
+            path.display(),
+            err
+          ),
+        }
+      })
+      .map_err(Error::Spawn)?;
+    Ok(())
+  }
+}
+
+/// Tells apart versions of the file at `path`, `None` if it can't be read.
+fn version(path: &Path) -> Option<(SystemTime, u64)> {
+  let metadata = fs::metadata(path).ok()?;
+  Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Deserializes a value from its name, or `None` from a null.
+fn parsed<'de, D, T>(deserializer: D) -> StdResult<Option<T>, D::Error>
+where
+  D: Deserializer<'de>,
+  T: FromStr,
+  T::Err: Display,
+{
+  match Option::<String>::deserialize(deserializer)? {
+    Some(text) => text.parse().map(Some).map_err(de::Error::custom),
+    None => Ok(None),
+  }
+}
+
+/// A number, or a text with a unit.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Quantity {
+  Number(u64),
+  Text(String),
+}
+
+fn size<'de, D: Deserializer<'de>>(deserializer: D) -> StdResult<Option<u128>, D::Error> {
+  match Option::<Quantity>::deserialize(deserializer)? {
+    Some(Quantity::Number(bytes)) => Ok(Some(bytes.into())),
+    Some(Quantity::Text(text)) => units::parse_size(&text).map(Some).map_err(de::Error::custom),
+    None => Ok(None),
+  }
+}
+
+fn duration<'de, D: Deserializer<'de>>(deserializer: D) -> StdResult<Option<Duration>, D::Error> {
+  match Option::<Quantity>::deserialize(deserializer)? {
+    Some(Quantity::Number(seconds)) => Ok(Some(Duration::from_secs(seconds))),
+    Some(Quantity::Text(text)) => units::parse_duration(&text).map(Some).map_err(de::Error::custom),
+    None => Ok(None),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Checks a config equivalent to the ones below.
+  fn check(config: LoggerConfig) {
+    assert_eq!(config.level, Some(LevelFilter::Info));
+    assert_eq!(config.format, Some(LogFormat::Json));
+    assert_eq!(config.rotation.max_file_size, Some(10 << 20));
+    assert_eq!(config.rotation.max_rotated_files, Some(3));
+    assert_eq!(config.rotation.period, Some(RotationPeriod::Daily));
+    assert_eq!(config.rotation.max_age, Some(Duration::from_secs(7 * 24 * 60 * 60)));
+    let [console, dir] = &config.targets[..] else {
+      panic!("expected two targets, got {:?}", config.targets);
+    };
+    assert_eq!(console.kind, TargetKind::Console);
+    assert_eq!(dir.kind, TargetKind::Dir);
+    assert_eq!(dir.path.as_deref(), Some(Path::new("logs")));
+    assert_eq!(dir.level, Some(LevelFilter::Warn));
+    assert_eq!(dir.rotation.as_ref().and_then(|rotation| rotation.max_file_size), Some(1024));
+  }
+
+  #[test]
+  fn parses_toml() {
+    let text = r#"
+      level = "info"
+      format = "json"
+
+      [rotation]
+      max_file_size = "10MB"
+      max_rotated_files = 3
+      period = "daily"
+      max_age = "7d"
+
+      [[targets]]
+      kind = "console"
+
+      [[targets]]
+      kind = "dir"
+      path = "logs"
+      level = "warn"
+      rotation = { max_file_size = 1024 }
+    "#;
+    check(LoggerConfig::parse(text, ConfigFormat::Toml).unwrap());
+  }
+
+  #[test]
+  fn parses_yaml() {
+    let text = "
+      level: info
+      format: json
+      rotation:
+        max_file_size: 10MB
+        max_rotated_files: 3
+        period: daily
+        max_age: 604800
+      targets:
+        - kind: console
+          level: ~
+        - kind: dir
+          path: logs
+          level: warn
+          rotation:
+            max_file_size: 1024
+    ";
+    check(LoggerConfig::parse(text, ConfigFormat::Yaml).unwrap());
+  }
+
+  #[test]
+  fn parses_json() {
+    let text = r#"{
+      "level": "info",
+      "format": "json",
+      "format_template": null,
+      "rotation": {
+        "max_file_size": "10MB",
+        "max_rotated_files": 3,
+        "period": "daily",
+        "clock": null,
+        "max_total_size": null,
+        "max_age": "7d"
+      },
+      "targets": [
+        {"kind": "console", "level": null, "format": null},
+        {"kind": "dir", "path": "logs", "level": "warn", "rotation": {"max_file_size": 1024}}
+      ]
+    }"#;
+    check(LoggerConfig::parse(text, ConfigFormat::Json).unwrap());
+  }
+
+  #[test]
+  fn rejects_invalid_configs() {
+    let cases = [
+      ("level = \"loud\"", ConfigFormat::Toml),
+      ("[rotation]\nmax_file_size = \"10PB\"", ConfigFormat::Toml),
+      ("colour: true", ConfigFormat::Yaml),
+      ("targets:\n  - kind: printer", ConfigFormat::Yaml),
+      (r#"{"rotation": {"max_age": "1y"}}"#, ConfigFormat::Json),
+      (r#"{"level": 3}"#, ConfigFormat::Json),
+    ];
+    for (text, format) in cases {
+      let result = LoggerConfig::parse(text, format);
+      assert!(matches!(result, Err(Error::InvalidConfig(_))), "`{}` was accepted", text);
+    }
+  }
+}
@@ -0,0 +1,230 @@
+use std::{
+  env::{self, VarError},
+  path::PathBuf,
+  result::Result as StdResult,
+  str::FromStr,
+};
+
+use log::LevelFilter;
+
+use crate::{
+  logger::{Error, LogLevel, LogTarget, LoggerBuilder, Result, Target},
+  syslog::Syslog,
+  units,
+};
+
+impl LoggerBuilder {
+  /// Returns a builder configured by the environment variables of `prefix`,
+  /// as set by `env`. Builder calls made afterwards override them.
+  pub fn from_env(prefix: &str) -> Result<Self> {
+    Self::new().env(prefix)
+  }
+
+  /// Overrides the configuration with the environment variables named
+  /// `{prefix}_*`, e.g. `YASLOG_LEVEL` for the prefix `YASLOG`. Unset and
+  /// empty variables are skipped:
+  ///
+  /// - `LEVEL`, `DIRECTIVES`, `STDERR_LEVEL`: like `level`, `directives` and
+  ///   `stderr_level`, `LEVEL` also taking `off`.
+  /// - `FORMAT` (`text`, `json` or `logfmt`) and `FORMAT_TEMPLATE`.
+  /// - `TARGETS`: comma-separated `console`, `stderr`, `split_console`,
+  ///   `dir:<path>`, `file:<path>`, `syslog` for the local daemon and
+  ///   `syslog:unix:<path>`, `syslog:udp:<address>` or `syslog:tcp:<address>`,
+  ///   replacing the targets.
+  /// - `DIR`: moves the dir targets to this dir, adding one if there is none.
+  /// - `MAX_FILE_SIZE` and `MAX_TOTAL_SIZE`: sizes like `10MB`.
+  /// - `MAX_ROTATED_FILES`.
+  /// - `MAX_AGE`: a duration like `7d`.
+  /// - `ROTATION_PERIOD`: `hourly`, `daily`, `weekly` or a duration.
+  /// - `ROTATION_CLOCK`: `local` or `utc`.
+  /// - `COMPRESSION`: `gzip`, `zstd` or `none`.
+  pub fn env(mut self, prefix: &str) -> Result<Self> {
+    let var = |name: &str| -> Result<Option<(String, String)>> {
+      let name = format!("{}_{}", prefix, name);
+      match env::var(&name) {
+        Ok(value) if value.trim().is_empty() => Ok(None),
+        Ok(value) => Ok(Some((name, value.trim().to_string()))),
+        Err(VarError::NotPresent) => Ok(None),
+        Err(VarError::NotUnicode(_)) => {
+          Err(Error::InvalidConfig(format!("`{}` is not valid unicode", name)))
+        }
+      }
+    };
+
+    if let Some((name, value)) = var("LEVEL")? {
+      self.level = parse::<LevelFilter>(&name, &value)?;
+    }
+    if let Some((_, value)) = var("DIRECTIVES")? {
+      self.directives = value;
+    }
+    if let Some((name, value)) = var("STDERR_LEVEL")? {
+      self.stderr_level = parse::<LogLevel>(&name, &value)?;
+    }
+    if let Some((name, value)) = var("FORMAT")? {
+      self.format = parse(&name, &value)?;
+    }
+    if let Some((_, value)) = var("FORMAT_TEMPLATE")? {
+      self.format_template = value;
+    }
+    if let Some((name, value)) = var("TARGETS")? {
+      self.targets = value
+        .split(',')
+        .map(str::trim)
+        .filter(|target| !target.is_empty())
+        .map(|target| with_name(&name, parse_target(target)))
+        .collect::<Result<_>>()?;
+    }
+    if let Some((_, value)) = var("DIR")? {
+      let mut moved = false;
+      for target in &mut self.targets {
+        if let LogTarget::Dir(dir) = &mut target.target {
+          *dir = PathBuf::from(&value);
+          moved = true;
+        }
+      }
+      if !moved {
+        self.targets.push(LogTarget::Dir(PathBuf::from(value)).into());
+      }
+    }
+    if let Some((name, value)) = var("MAX_FILE_SIZE")? {
+      self.rotation.max_file_size = with_name(&name, units::parse_size(&value))?;
+    }
+    if let Some((name, value)) = var("MAX_ROTATED_FILES")? {
+      self.rotation.max_rotated_files = parse(&name, &value)?;
+    }
+    if let Some((name, value)) = var("MAX_TOTAL_SIZE")? {
+      self.rotation.max_total_size = Some(with_name(&name, units::parse_size(&value))?);
+    }
+    if let Some((name, value)) = var("MAX_AGE")? {
+      self.rotation.max_age = Some(with_name(&name, units::parse_duration(&value))?);
+    }
+    if let Some((name, value)) = var("ROTATION_PERIOD")? {
+      self.rotation.period = Some(parse(&name, &value)?);
+    }
+    if let Some((name, value)) = var("ROTATION_CLOCK")? {
+      self.rotation.clock = parse(&name, &value)?;
+    }
+    if let Some((name, value)) = var("COMPRESSION")? {
+      self.rotation.compression =
+        if value.eq_ignore_ascii_case("none") { None } else { Some(parse(&name, &value)?) };
+    }
+    Ok(self)
+  }
+}
+
+fn parse<T: FromStr>(name: &str, value: &str) -> Result<T> {
+  value.parse().map_err(|_| Error::InvalidConfig(format!("invalid `{}`: `{}`", name, value)))
+}
+
+fn with_name<T>(name: &str, result: StdResult<T, String>) -> Result<T> {
+  result.map_err(|err| Error::InvalidConfig(format!("invalid `{}`: {}", name, err)))
+}
+
+/// Parses a target of `TARGETS`.
+pub(crate) fn parse_target(target: &str) -> StdResult<Target, String> {
+  let target = match target.split_once(':') {
+    Some(("dir", path)) => LogTarget::Dir(PathBuf::from(path)),
+    Some(("file", path)) => LogTarget::File(PathBuf::from(path)),
+    Some(("syslog", daemon)) => LogTarget::Syslog(match daemon.split_once(':') {
+      #[cfg(unix)]
+      Some(("unix", path)) => Syslog::unix(path),
+      Some(("udp", address)) => Syslog::udp(address),
+      Some(("tcp", address)) => Syslog::tcp(address),
+      _ => return Err(format!("unknown syslog daemon `{}`", daemon)),
+    }),
+    #[cfg(unix)]
+    None if target == "syslog" => LogTarget::Syslog(Syslog::local()),
+    _ => match target {
+      "console" => LogTarget::Console,
+      "stderr" => LogTarget::Stderr,
+      "split_console" => LogTarget::SplitConsole,
+      _ => return Err(format!("unknown target `{}`", target)),
+    },
+  };
+  Ok(target.into())
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{path::Path, time::Duration};
+
+  use super::*;
+  use crate::{format::LogFormat, rotating_file::RotationPeriod};
+
+  /// Sets the variables of `prefix`, which must be unique to the test as the
+  /// tests share the environment.
+  fn set_vars(prefix: &str, vars: &[(&str, &str)]) {
+    for (name, value) in vars {
+      env::set_var(format!("{}_{}", prefix, name), value);
+    }
+  }
+
+  #[test]
+  fn reads_the_prefixed_variables() {
+    set_vars(
+      "YASLOG_TEST_ENV_ALL",
+      &[
+        ("LEVEL", "debug"),
+        ("DIRECTIVES", "hyper=warn"),
+        ("STDERR_LEVEL", " "),
+        ("FORMAT", "json"),
+        ("TARGETS", "console, dir:logs,,file:out/app.log"),
+        ("DIR", "var/log"),
+        ("MAX_FILE_SIZE", "10MB"),
+        ("MAX_ROTATED_FILES", "3"),
+        ("MAX_AGE", "7d"),
+        ("ROTATION_PERIOD", "daily"),
+        ("COMPRESSION", "none"),
+      ],
+    );
+    let builder = LoggerBuilder::from_env("YASLOG_TEST_ENV_ALL").unwrap();
+    assert_eq!(builder.level, LevelFilter::Debug);
+    assert_eq!(builder.directives, "hyper=warn");
+    assert_eq!(builder.stderr_level, LoggerBuilder::new().stderr_level);
+    assert_eq!(builder.format, LogFormat::Json);
+    assert_eq!(builder.targets.len(), 3);
+    assert!(matches!(builder.targets[0].target, LogTarget::Console));
+    assert!(
+      matches!(&builder.targets[1].target, LogTarget::Dir(dir) if dir == Path::new("var/log"))
+    );
+    assert!(
+      matches!(&builder.targets[2].target, LogTarget::File(file) if file == Path::new("out/app.log"))
+    );
+    assert_eq!(builder.rotation.max_file_size, 10 * 1024 * 1024);
+    assert_eq!(builder.rotation.max_rotated_files, 3);
+    assert_eq!(builder.rotation.max_age, Some(Duration::from_secs(7 * 24 * 60 * 60)));
+    assert_eq!(builder.rotation.period, Some(RotationPeriod::Daily));
+    assert!(builder.rotation.compression.is_none());
+  }
+
+  #[test]
+  fn dir_adds_a_dir_target() {
+    set_vars("YASLOG_TEST_ENV_DIR", &[("TARGETS", "console"), ("DIR", "logs")]);
+    let builder = LoggerBuilder::from_env("YASLOG_TEST_ENV_DIR").unwrap();
+    assert_eq!(builder.targets.len(), 2);
+    assert!(matches!(&builder.targets[1].target, LogTarget::Dir(dir) if dir == Path::new("logs")));
+  }
+
+  #[test]
+  fn unset_variables_keep_the_builder() {
+    let builder = LoggerBuilder::new().level(LogLevel::Warn).env("YASLOG_TEST_ENV_UNSET").unwrap();
+    assert_eq!(builder.level, LevelFilter::Warn);
+  }
+
+  #[test]
+  fn rejects_invalid_values() {
+    for (index, var) in [
+      ("LEVEL", "loud"),
+      ("TARGETS", "console,bogus"),
+      ("MAX_FILE_SIZE", "10PB"),
+      ("MAX_AGE", "1y"),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+      let prefix = format!("YASLOG_TEST_ENV_INVALID_{}", index);
+      set_vars(&prefix, &[var]);
+      assert!(LoggerBuilder::from_env(&prefix).is_err(), "{:?} was accepted", var);
+    }
+  }
+}
@@ -0,0 +1,82 @@
+use std::{
+  error::Error as StdError,
+  fmt::{self, Display},
+  io,
+  path::PathBuf,
+  result::Result as StdResult,
+};
+
+use log::SetLoggerError;
+
+pub type Result<T> = StdResult<T, Error>;
+
+/// Errors of building, configuring and reloading a logger.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+  /// A global logger was installed already.
+  AlreadyInitialized,
+  /// The dir of a file target couldn't be created.
+  CreateDir { path: PathBuf, source: io::Error },
+  /// The log file of a file target couldn't be opened.
+  OpenFile { path: PathBuf, source: io::Error },
+  /// The log file of a file target couldn't be rotated, or its rotated files
+  /// cleaned up, when opening it.
+  Rotate { path: PathBuf, source: io::Error },
+  /// The syslog daemon of a syslog target couldn't be reached.
+  Connect { address: String, source: io::Error },
+  /// The config file couldn't be read.
+  ReadConfig { path: PathBuf, source: io::Error },
+  /// A config, format template, directive or environment variable is invalid.
+  InvalidConfig(String),
+  /// A background thread couldn't be started.
+  Spawn(io::Error),
+  /// The logger was shut down, so it can't be reloaded.
+  ShutDown,
+}
+
+impl Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Error::AlreadyInitialized => f.write_str("a global logger is installed already"),
+      Error::CreateDir { path, source } => {
+        write!(f, "failed to create the log dir {}: {}", path.display(), source)
+      }
+      Error::OpenFile { path, source } => {
+        write!(f, "failed to open the log file {}: {}", path.display(), source)
+      }
+      Error::Rotate { path, source } => {
+        write!(f, "failed to rotate the log file {}: {}", path.display(), source)
+      }
+      Error::Connect { address, source } => {
+        write!(f, "failed to connect to the syslog daemon at {}: {}", address, source)
+      }
+      Error::ReadConfig { path, source } => {
+        write!(f, "failed to read the config file {}: {}", path.display(), source)
+      }
+      Error::InvalidConfig(message) => write!(f, "invalid logger config: {}", message),
+      Error::Spawn(source) => write!(f, "failed to start a logger thread: {}", source),
+      Error::ShutDown => f.write_str("the logger was shut down"),
+    }
+  }
+}
+
+impl StdError for Error {
+  fn source(&self) -> Option<&(dyn StdError + 'static)> {
+    match self {
+      Error::CreateDir { source, .. }
+      | Error::OpenFile { source, .. }
+      | Error::Rotate { source, .. }
+      | Error::Connect { source, .. }
+      | Error::ReadConfig { source, .. }
+      | Error::Spawn(source) => Some(source),
+      Error::AlreadyInitialized | Error::InvalidConfig(_) | Error::ShutDown => None,
+    }
+  }
+}
+
+impl From<SetLoggerError> for Error {
+  fn from(_: SetLoggerError) -> Self {
+    Error::AlreadyInitialized
+  }
+}
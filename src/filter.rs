@@ -0,0 +1,184 @@
+use std::cmp::Reverse;
+
+use log::{LevelFilter, Metadata};
+
+/// Levels of the records to log, a default one and ones for modules, parsed
+/// from directives like `info,my_app::db=trace,hyper=warn`.
+///
+/// A module's level applies to its records and those of its submodules,
+/// matched against the record's target, and the longest matching module wins.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Directives {
+  level: LevelFilter,
+  /// Modules and their levels, longest module first.
+  modules: Vec<(String, LevelFilter)>,
+}
+
+impl Directives {
+  pub(crate) fn new(level: LevelFilter) -> Self {
+    Self { level, modules: Vec::new() }
+  }
+
+  /// Parses comma-separated directives on top of the default `level`. A
+  /// directive is `module=level`, a bare level setting the default one or a
+  /// bare module enabling all of its records.
+  pub(crate) fn parse(level: LevelFilter, directives: &str) -> Result<Self, String> {
+    let mut parsed = Self::new(level);
+    for directive in directives.split(',').map(str::trim).filter(|directive| !directive.is_empty())
+    {
+      match directive.split_once('=') {
+        Some((module, level)) => {
+          let module = module.trim();
+          if module.is_empty() {
+            return Err(format!("missing module in directive `{}`", directive));
+          }
+          match level.trim().parse() {
+            Ok(level) => parsed.set(module, level),
+            Err(_) => return Err(format!("unknown level in directive `{}`", directive)),
+          }
+        }
+        None => match directive.parse() {
+          Ok(level) => parsed.level = level,
+          Err(_) => parsed.set(directive, LevelFilter::Trace),
+        },
+      }
+    }
+    Ok(parsed)
+  }
+
+  /// Returns the default level.
+  pub(crate) fn level(&self) -> LevelFilter {
+    self.level
+  }
+
+  pub(crate) fn set_level(&mut self, level: LevelFilter) {
+    self.level = level;
+  }
+
+  /// Sets the level of `module`, replacing its previous one.
+  pub(crate) fn set(&mut self, module: &str, level: LevelFilter) {
+    match self.modules.iter_mut().find(|(name, _)| name == module) {
+      Some((_, old)) => *old = level,
+      None => {
+        self.modules.push((module.to_string(), level));
+        self.modules.sort_by_key(|(module, _)| Reverse(module.len()));
+      }
+    }
+  }
+
+  /// Removes the level of `module`, which falls back to the one of its
+  /// closest parent or the default one.
+  pub(crate) fn remove(&mut self, module: &str) {
+    self.modules.retain(|(name, _)| name != module);
+  }
+
+  /// Returns the level of the records of `target`.
+  pub(crate) fn level_for(&self, target: &str) -> LevelFilter {
+    self
+      .modules
+      .iter()
+      .find(|(module, _)| {
+        target
+          .strip_prefix(module.as_str())
+          .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+      })
+      .map_or(self.level, |(_, level)| *level)
+  }
+
+  /// Returns the most verbose level of any module.
+  pub(crate) fn max_level(&self) -> LevelFilter {
+    self.modules.iter().map(|(_, level)| *level).fold(self.level, Ord::max)
+  }
+
+  pub(crate) fn enabled(&self, metadata: &Metadata) -> bool {
+    metadata.level() <= self.level_for(metadata.target())
+  }
+}
+
+/// Levels of a logger: the default directives, which can change at runtime,
+/// and the ones of the targets which have their own.
+#[derive(Clone, Debug)]
+pub(crate) struct Levels {
+  pub(crate) default: Directives,
+  pub(crate) targets: Vec<Directives>,
+}
+
+impl Levels {
+  /// Returns whether any target may log the record.
+  pub(crate) fn enabled(&self, metadata: &Metadata) -> bool {
+    self.default.enabled(metadata) || self.targets.iter().any(|target| target.enabled(metadata))
+  }
+
+  /// Returns the most verbose level of any target.
+  pub(crate) fn max_level(&self) -> LevelFilter {
+    self.targets.iter().map(Directives::max_level).fold(self.default.max_level(), Ord::max)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_directives() {
+    let directives =
+      Directives::parse(LevelFilter::Info, "warn, my_app::db=trace,hyper=off").unwrap();
+    assert_eq!(directives.level(), LevelFilter::Warn);
+    assert_eq!(directives.level_for("my_app::db"), LevelFilter::Trace);
+    assert_eq!(directives.level_for("hyper"), LevelFilter::Off);
+    assert_eq!(directives.level_for("my_app"), LevelFilter::Warn);
+    assert_eq!(directives.max_level(), LevelFilter::Trace);
+  }
+
+  #[test]
+  fn bare_module_enables_everything() {
+    let directives = Directives::parse(LevelFilter::Error, "my_app,").unwrap();
+    assert_eq!(directives.level(), LevelFilter::Error);
+    assert_eq!(directives.level_for("my_app::db"), LevelFilter::Trace);
+  }
+
+  #[test]
+  fn rejects_invalid_directives() {
+    assert!(Directives::parse(LevelFilter::Info, "=debug").is_err());
+    assert!(Directives::parse(LevelFilter::Info, "my_app=loud").is_err());
+  }
+
+  #[test]
+  fn longest_module_wins() {
+    let directives =
+      Directives::parse(LevelFilter::Info, "a=debug,a::b::c=error,a::b=warn").unwrap();
+    assert_eq!(directives.level_for("a::x"), LevelFilter::Debug);
+    assert_eq!(directives.level_for("a::b"), LevelFilter::Warn);
+    assert_eq!(directives.level_for("a::b::d"), LevelFilter::Warn);
+    assert_eq!(directives.level_for("a::b::c::e"), LevelFilter::Error);
+  }
+
+  #[test]
+  fn modules_match_whole_path_segments() {
+    let directives = Directives::parse(LevelFilter::Info, "my_app=debug").unwrap();
+    assert_eq!(directives.level_for("my_app_extra"), LevelFilter::Info);
+    assert_eq!(directives.level_for("my_app:x"), LevelFilter::Info);
+    assert_eq!(directives.level_for("my_app::x"), LevelFilter::Debug);
+  }
+
+  #[test]
+  fn sets_and_removes_module_levels() {
+    let mut directives = Directives::parse(LevelFilter::Info, "a=debug").unwrap();
+    directives.set("a::b", LevelFilter::Error);
+    directives.set("a", LevelFilter::Trace);
+    assert_eq!(directives.level_for("a::b::c"), LevelFilter::Error);
+    assert_eq!(directives.level_for("a::x"), LevelFilter::Trace);
+    directives.remove("a::b");
+    assert_eq!(directives.level_for("a::b::c"), LevelFilter::Trace);
+    directives.remove("a");
+    assert_eq!(directives.level_for("a::b::c"), LevelFilter::Info);
+    directives.set_level(LevelFilter::Off);
+    assert_eq!(directives.level_for("a::b::c"), LevelFilter::Off);
+  }
+
+  #[test]
+  fn later_directives_replace_earlier_ones() {
+    let directives = Directives::parse(LevelFilter::Info, "a=debug,a=error").unwrap();
+    assert_eq!(directives.level_for("a"), LevelFilter::Error);
+  }
+}
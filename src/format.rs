@@ -0,0 +1,708 @@
+use std::{
+  env,
+  fmt::{self, Display, Write},
+  io::IsTerminal,
+  process,
+  str::FromStr,
+  sync::Arc,
+  thread,
+};
+
+use chrono::{
+  format::{Item, StrftimeItems},
+  DateTime, Local, SecondsFormat,
+};
+
+use fern::{
+  colors::{Color, ColoredLevelConfig},
+  FormatCallback,
+};
+
+use log::{
+  kv::{self, Key, Value, VisitSource},
+  Record,
+};
+
+/// Formats of the log records.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+  /// Lines laid out by the builder's format template.
+  #[default]
+  Text,
+  /// JSON Lines, one object per record with `timestamp` (RFC 3339), `level`,
+  /// `target`, `module`, `file`, `line`, `thread` and `message`, and the
+  /// record's key-values in a `fields` object.
+  Json,
+  /// logfmt lines, `ts=... level=info target=... line=... msg="..."`, with
+  /// the fields of `Json`, missing ones left out, and the record's key-values
+  /// appended. Spaces, `=` and quotes in their keys are replaced by `_`.
+  Logfmt,
+}
+
+impl FromStr for LogFormat {
+  type Err = String;
+
+  /// Parses `text`, `json` or `logfmt`, ignoring case.
+  fn from_str(format: &str) -> Result<Self, String> {
+    match format.to_ascii_lowercase().as_str() {
+      "text" => Ok(LogFormat::Text),
+      "json" => Ok(LogFormat::Json),
+      "logfmt" => Ok(LogFormat::Logfmt),
+      _ => Err(format!("unknown format `{}`", format)),
+    }
+  }
+}
+
+pub(crate) type Formatter = Box<dyn Fn(FormatCallback, &fmt::Arguments, &Record) + Sync + Send>;
+
+/// Template of the default text layout, `[time]<level>[target:line] message`.
+pub(crate) const DEFAULT_TEMPLATE: &str =
+  "[{time:%Y-%m-%d %H:%M:%S}]<{level}>[{target}:{line}] {msg}";
+
+/// A text layout compiled from a template such as
+/// `{time:%H:%M:%S%.3f} {level:>5} {module}::{line} - {msg}`.
+///
+/// Every `{field}` may be padded to a width and aligned with `{field:<10}`,
+/// `{field:>10}` or `{field:^10}`, except `{time}`, whose spec is a strftime
+/// format instead. The fields are `time`, `level`, `target`, `module`,
+/// `file`, `line`, `thread`, `thread_id`, `pid`, `hostname`, `msg` and `kv`,
+/// the record's key-values as `key=value` pairs, which are appended to the
+/// line if the template has no `{kv}`. `{{` and `}}` stand for literal braces.
+pub(crate) struct TextLayout {
+  parts: Vec<Part>,
+  has_key_values: bool,
+}
+
+enum Part {
+  Literal(String),
+  Time(Vec<Item<'static>>),
+  Field(Field, Option<Padding>),
+}
+
+enum Field {
+  Level,
+  Target,
+  Module,
+  File,
+  Line,
+  Thread,
+  ThreadId,
+  Message,
+  KeyValues,
+  Constant(String),
+}
+
+struct Padding {
+  align: Align,
+  width: usize,
+}
+
+enum Align {
+  Left,
+  Right,
+  Center,
+}
+
+impl TextLayout {
+  pub(crate) fn parse(template: &str) -> Result<Self, String> {
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+      match c {
+        '{' if chars.as_str().starts_with('{') => {
+          chars.next();
+          literal.push('{');
+        }
+        '}' if chars.as_str().starts_with('}') => {
+          chars.next();
+          literal.push('}');
+        }
+        '}' => return Err(format!("unmatched `}}` in `{}`", template)),
+        '{' => {
+          let rest = chars.as_str();
+          let close = match rest.find('}') {
+            Some(close) => close,
+            None => return Err(format!("unclosed field in `{}`", template)),
+          };
+          let (name, spec) = match rest[..close].split_once(':') {
+            Some((name, spec)) => (name, Some(spec)),
+            None => (&rest[..close], None),
+          };
+          chars = rest[close + 1..].chars();
+
+          if !literal.is_empty() {
+            parts.push(Part::Literal(literal.split_off(0)));
+          }
+          if name == "time" {
+            let format = spec.unwrap_or("%Y-%m-%d %H:%M:%S");
+            let items = StrftimeItems::new(format)
+              .parse_to_owned()
+              .map_err(|_| format!("invalid time format `{}` in `{}`", format, template))?;
+            parts.push(Part::Time(items));
+            continue;
+          }
+          let field = match name {
+            "level" => Field::Level,
+            "target" => Field::Target,
+            "module" => Field::Module,
+            "file" => Field::File,
+            "line" => Field::Line,
+            "thread" => Field::Thread,
+            "thread_id" => Field::ThreadId,
+            "msg" => Field::Message,
+            "kv" => Field::KeyValues,
+            "pid" => Field::Constant(process::id().to_string()),
+            "hostname" => Field::Constant(
+              hostname::get().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default(),
+            ),
+            _ => return Err(format!("unknown field `{{{}}}` in `{}`", name, template)),
+          };
+          let padding = match spec {
+            Some(spec) => match Padding::parse(spec) {
+              Some(padding) => Some(padding),
+              None => return Err(format!("invalid padding `{}` in `{}`", spec, template)),
+            },
+            None => None,
+          };
+          parts.push(Part::Field(field, padding));
+        }
+        c => literal.push(c),
+      }
+    }
+    if !literal.is_empty() {
+      parts.push(Part::Literal(literal));
+    }
+    let has_key_values = parts.iter().any(|part| matches!(part, Part::Field(Field::KeyValues, _)));
+    Ok(Self { parts, has_key_values })
+  }
+}
+
+impl Padding {
+  fn parse(spec: &str) -> Option<Self> {
+    let (align, width) = match spec.chars().next()? {
+      '<' => (Align::Left, &spec[1..]),
+      '>' => (Align::Right, &spec[1..]),
+      '^' => (Align::Center, &spec[1..]),
+      _ => (Align::Left, spec),
+    };
+    Some(Self { align, width: width.parse().ok()? })
+  }
+}
+
+/// A value padded to the width of its field.
+struct Padded<'a, T>(Option<&'a Padding>, T);
+
+impl<T: Display> Display for Padded<'_, T> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let value = &self.1;
+    match self.0 {
+      None => write!(f, "{}", value),
+      Some(Padding { align: Align::Left, width }) => write!(f, "{:<width$}", value, width = width),
+      Some(Padding { align: Align::Right, width }) => write!(f, "{:>width$}", value, width = width),
+      Some(Padding { align: Align::Center, width }) => {
+        write!(f, "{:^width$}", value, width = width)
+      }
+    }
+  }
+}
+
+/// A value wrapped in the escape sequences of a color.
+struct Colored<T>(Color, T);
+
+impl<T: Display> Display for Colored<T> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "\x1B[{}m{}\x1B[0m", self.0.to_fg_str(), self.1)
+  }
+}
+
+/// A record laid out by a `TextLayout`.
+struct Line<'a> {
+  layout: &'a TextLayout,
+  record: &'a Record<'a>,
+  message: &'a fmt::Arguments<'a>,
+  colors: Option<&'a ColoredLevelConfig>,
+  now: DateTime<Local>,
+}
+
+impl Display for Line<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let record = self.record;
+    for part in &self.layout.parts {
+      match part {
+        Part::Literal(literal) => f.write_str(literal)?,
+        Part::Time(items) => write!(f, "{}", self.now.format_with_items(items.iter()))?,
+        Part::Field(field, padding) => {
+          let padding = padding.as_ref();
+          match field {
+            Field::Level => match self.colors {
+              Some(colors) => {
+                let level = Padded(padding, record.level()).to_string();
+                Colored(colors.get_color(&record.level()), level).fmt(f)?
+              }
+              None => Padded(padding, record.level()).fmt(f)?,
+            },
+            Field::Target => Padded(padding, record.target()).fmt(f)?,
+            Field::Module => Padded(padding, record.module_path().unwrap_or_default()).fmt(f)?,
+            Field::File => Padded(padding, record.file().unwrap_or_default()).fmt(f)?,
+            Field::Line => Padded(padding, record.line().unwrap_or_default()).fmt(f)?,
+            Field::Thread => {
+              Padded(padding, thread::current().name().unwrap_or("<unnamed>")).fmt(f)?
+            }
+            Field::ThreadId => {
+              let id = format!("{:?}", thread::current().id());
+              let id: String = id.chars().filter(char::is_ascii_digit).collect();
+              Padded(padding, id).fmt(f)?
+            }
+            Field::Message => match padding {
+              Some(_) => Padded(padding, self.message.to_string()).fmt(f)?,
+              None => self.message.fmt(f)?,
+            },
+            Field::KeyValues => match padding {
+              Some(_) => Padded(padding, TextPairs(record).to_string()).fmt(f)?,
+              None => TextPairs(record).fmt(f)?,
+            },
+            Field::Constant(value) => Padded(padding, value).fmt(f)?,
+          }
+        }
+      }
+    }
+    if !self.layout.has_key_values && record.key_values().count() > 0 {
+      write!(f, " {}", TextPairs(record))?;
+    }
+    Ok(())
+  }
+}
+
+/// The key-values of a record as space separated `key=value` pairs.
+struct TextPairs<'a>(&'a Record<'a>);
+
+impl Display for TextPairs<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let mut separator = "";
+    for_each_pair(self.0, |key, value| {
+      write!(f, "{}{}={}", separator, LogfmtKey(key), LogfmtValue(value))?;
+      separator = " ";
+      Ok(())
+    })
+  }
+}
+
+/// Calls `f` with every key-value of `record`.
+fn for_each_pair<F>(record: &Record, f: F) -> fmt::Result
+where
+  F: for<'kvs> FnMut(Key<'kvs>, Value<'kvs>) -> fmt::Result,
+{
+  struct Visitor<F>(F);
+
+  impl<'kvs, F> VisitSource<'kvs> for Visitor<F>
+  where
+    F: FnMut(Key<'kvs>, Value<'kvs>) -> fmt::Result,
+  {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+      (self.0)(key, value).map_err(|_| kv::Error::msg("failed to format a key-value"))
+    }
+  }
+
+  record.key_values().visit(&mut Visitor(f)).map_err(|_| fmt::Error)
+}
+
+/// A record as a JSON object.
+struct JsonLine<'a> {
+  record: &'a Record<'a>,
+  message: &'a fmt::Arguments<'a>,
+  now: DateTime<Local>,
+}
+
+impl Display for JsonLine<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let record = self.record;
+    write!(
+      f,
+      "{{\"timestamp\":{}",
+      JsonStr(self.now.to_rfc3339_opts(SecondsFormat::Millis, false))
+    )?;
+    write!(f, ",\"level\":{}", JsonStr(record.level()))?;
+    write!(f, ",\"target\":{}", JsonStr(record.target()))?;
+    write!(f, ",\"module\":{}", JsonOption(record.module_path().map(JsonStr)))?;
+    write!(f, ",\"file\":{}", JsonOption(record.file().map(JsonStr)))?;
+    write!(f, ",\"line\":{}", JsonOption(record.line()))?;
+    write!(f, ",\"thread\":{}", JsonOption(thread::current().name().map(JsonStr)))?;
+    write!(f, ",\"message\":{}", JsonStr(self.message))?;
+    if record.key_values().count() > 0 {
+      let mut separator = "";
+      f.write_str(",\"fields\":{")?;
+      for_each_pair(record, |key, value| {
+        write!(f, "{}{}:", separator, JsonStr(key))?;
+        // Values JSON can't represent, e.g. maps with non-string keys, are
+        // written as strings rather than failing the whole line.
+        match serde_json::to_string(&value) {
+          Ok(json) => f.write_str(&json)?,
+          Err(_) => JsonStr(&value).fmt(f)?,
+        }
+        separator = ",";
+        Ok(())
+      })?;
+      f.write_char('}')?;
+    }
+    f.write_char('}')
+  }
+}
+
+/// A value written as a JSON string.
+struct JsonStr<T>(T);
+
+impl<T: Display> Display for JsonStr<T> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_char('"')?;
+    write!(Escaper(f), "{}", self.0)?;
+    f.write_char('"')
+  }
+}
+
+/// A value written as is, or as `null` if there is none.
+struct JsonOption<T>(Option<T>);
+
+impl<T: Display> Display for JsonOption<T> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match &self.0 {
+      Some(value) => value.fmt(f),
+      None => f.write_str("null"),
+    }
+  }
+}
+
+/// Escapes everything written through it for a JSON or a quoted logfmt
+/// string.
+struct Escaper<'a, 'b>(&'a mut fmt::Formatter<'b>);
+
+impl Write for Escaper<'_, '_> {
+  fn write_str(&mut self, s: &str) -> fmt::Result {
+    let mut start = 0;
+    for (index, c) in s.char_indices() {
+      let escaped = match c {
+        '"' => Some("\\\""),
+        '\\' => Some("\\\\"),
+        '\n' => Some("\\n"),
+        '\r' => Some("\\r"),
+        '\t' => Some("\\t"),
+        c if c.is_control() => None,
+        _ => continue,
+      };
+      self.0.write_str(&s[start..index])?;
+      match escaped {
+        Some(escaped) => self.0.write_str(escaped)?,
+        None => write!(self.0, "\\u{:04x}", c as u32)?,
+      }
+      start = index + c.len_utf8();
+    }
+    self.0.write_str(&s[start..])
+  }
+}
+
+/// A record as a logfmt line.
+struct LogfmtLine<'a> {
+  record: &'a Record<'a>,
+  message: &'a fmt::Arguments<'a>,
+  now: DateTime<Local>,
+}
+
+impl Display for LogfmtLine<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let record = self.record;
+    let level = record.level().as_str().to_ascii_lowercase();
+    write!(f, "ts={}", self.now.to_rfc3339_opts(SecondsFormat::Millis, false))?;
+    write!(f, " level={}", level)?;
+    write!(f, " target={}", LogfmtValue(record.target()))?;
+    if let Some(module) = record.module_path() {
+      write!(f, " module={}", LogfmtValue(module))?;
+    }
+    if let Some(file) = record.file() {
+      write!(f, " file={}", LogfmtValue(file))?;
+    }
+    if let Some(line) = record.line() {
+      write!(f, " line={}", line)?;
+    }
+    if let Some(thread) = thread::current().name() {
+      write!(f, " thread={}", LogfmtValue(thread))?;
+    }
+    write!(f, " msg={}", LogfmtValue(self.message))?;
+    for_each_pair(record, |key, value| write!(f, " {}={}", LogfmtKey(key), LogfmtValue(value)))
+  }
+}
+
+/// A key written with spaces, `=`, quotes and control characters replaced by
+/// `_`, as logfmt keys can't be quoted.
+struct LogfmtKey<T>(T);
+
+impl<T: Display> Display for LogfmtKey<T> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let key = self.0.to_string();
+    if key.is_empty() {
+      return f.write_char('_');
+    }
+    for c in key.chars() {
+      let invalid = c == ' ' || c == '=' || c == '"' || c.is_control();
+      f.write_char(if invalid { '_' } else { c })?;
+    }
+    Ok(())
+  }
+}
+
+/// A value written bare, or quoted if it is empty or contains spaces, `=`,
+/// quotes or control characters.
+struct LogfmtValue<T>(T);
+
+impl<T: Display> Display for LogfmtValue<T> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let value = self.0.to_string();
+    let needs_quotes = value.is_empty()
+      || value.chars().any(|c| c == ' ' || c == '=' || c == '"' || c == '\\' || c.is_control());
+    if needs_quotes {
+      f.write_char('"')?;
+      Escaper(f).write_str(&value)?;
+      f.write_char('"')
+    } else {
+      f.write_str(&value)
+    }
+  }
+}
+
+/// Returns the formatter of `format`. Text is laid out after `layout`, with
+/// the level colored or not.
+pub(crate) fn formatter(format: LogFormat, layout: &Arc<TextLayout>, colored: bool) -> Formatter {
+  prefixed_formatter(format, layout, colored, |_, _| Ok(()))
+}
+
+/// Returns the formatter of `format`, writing `prefix` before every record.
+pub(crate) fn prefixed_formatter<P>(
+  format: LogFormat, layout: &Arc<TextLayout>, colored: bool, prefix: P,
+) -> Formatter
+where
+  P: Fn(&mut fmt::Formatter, &Record) -> fmt::Result + Sync + Send + 'static,
+{
+  let layout = layout.clone();
+  let colors = ColoredLevelConfig::new()
+    .info(Color::BrightBlue)
+    .warn(Color::BrightYellow)
+    .error(Color::BrightRed);
+  Box::new(move |out, message, record| {
+    let now = Local::now();
+    let line = match format {
+      LogFormat::Text => {
+        let colors = if colored { Some(&colors) } else { None };
+        AnyLine::Text(Line { layout: &layout, record, message, colors, now })
+      }
+      LogFormat::Json => AnyLine::Json(JsonLine { record, message, now }),
+      LogFormat::Logfmt => AnyLine::Logfmt(LogfmtLine { record, message, now }),
+    };
+    out.finish(format_args!("{}{}", Prefix(&prefix, record), line))
+  })
+}
+
+/// A record laid out in any format.
+enum AnyLine<'a> {
+  Text(Line<'a>),
+  Json(JsonLine<'a>),
+  Logfmt(LogfmtLine<'a>),
+}
+
+impl Display for AnyLine<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      AnyLine::Text(line) => line.fmt(f),
+      AnyLine::Json(line) => line.fmt(f),
+      AnyLine::Logfmt(line) => line.fmt(f),
+    }
+  }
+}
+
+struct Prefix<'a, P>(&'a P, &'a Record<'a>);
+
+impl<P: Fn(&mut fmt::Formatter, &Record) -> fmt::Result> Display for Prefix<'_, P> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    (self.0)(f, self.1)
+  }
+}
+
+/// Checks whether logs written to `stream` should be colored: `CLICOLOR_FORCE`
+/// forces colors, `NO_COLOR` or `CLICOLOR=0` disables them, and otherwise
+/// only terminals get them.
+pub(crate) fn use_colors<T: IsTerminal>(stream: &T) -> bool {
+  let var = |name: &str| env::var_os(name).filter(|value| !value.is_empty());
+  if var("CLICOLOR_FORCE").is_some_and(|value| value != "0") {
+    return true;
+  }
+  if var("NO_COLOR").is_some() || var("CLICOLOR").is_some_and(|value| value == "0") {
+    return false;
+  }
+  stream.is_terminal()
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::TimeZone;
+  use log::{kv::ToValue, Level};
+
+  use super::*;
+
+  fn now() -> DateTime<Local> {
+    Local.with_ymd_and_hms(2026, 10, 18, 9, 5, 7).unwrap()
+  }
+
+  fn render(template: &str, record: &Record) -> String {
+    let layout = TextLayout::parse(template).unwrap();
+    Line { layout: &layout, record, message: record.args(), colors: None, now: now() }.to_string()
+  }
+
+  fn line_with(template: &str, kvs: &[(&str, i32)]) -> String {
+    let record = Record::builder()
+      .args(format_args!("hello"))
+      .level(Level::Warn)
+      .target("my_app::db")
+      .line(Some(42))
+      .key_values(&kvs)
+      .build();
+    render(template, &record)
+  }
+
+  fn line(template: &str) -> String {
+    line_with(template, &[])
+  }
+
+  /// Renders a record as JSON or logfmt on an unnamed thread, so it has no
+  /// thread name, with its location if `located`.
+  fn structured<V: ToValue + Sync>(
+    format: LogFormat, message: &str, located: bool, kvs: &[(&str, V)],
+  ) -> String {
+    thread::scope(|scope| {
+      scope
+        .spawn(|| {
+          let render = |record: &Record| {
+            let (message, now) = (record.args(), now());
+            match format {
+              LogFormat::Json => JsonLine { record, message, now }.to_string(),
+              _ => LogfmtLine { record, message, now }.to_string(),
+            }
+          };
+          render(
+            &Record::builder()
+              .args(format_args!("{}", message))
+              .level(Level::Warn)
+              .target("my_app::db")
+              .module_path(located.then_some("my_app::db"))
+              .file(located.then_some("src/db.rs"))
+              .line(located.then_some(42))
+              .key_values(&kvs)
+              .build(),
+          )
+        })
+        .join()
+        .unwrap()
+    })
+  }
+
+  fn timestamp() -> String {
+    now().to_rfc3339_opts(SecondsFormat::Millis, false)
+  }
+
+  #[test]
+  fn renders_the_default_template() {
+    assert_eq!(line(DEFAULT_TEMPLATE), "[2026-10-18 09:05:07]<WARN>[my_app::db:42] hello");
+  }
+
+  #[test]
+  fn renders_time_formats() {
+    assert_eq!(line("{time}"), "2026-10-18 09:05:07");
+    assert_eq!(line("{time:%H:%M:%S%.3f} {msg}"), "09:05:07.000 hello");
+  }
+
+  #[test]
+  fn pads_fields() {
+    assert_eq!(line("[{level:>5}]"), "[ WARN]");
+    assert_eq!(line("[{level:<6}]"), "[WARN  ]");
+    assert_eq!(line("[{level:^8}]"), "[  WARN  ]");
+    assert_eq!(line("[{line:4}]"), "[42  ]");
+    assert_eq!(line("[{msg:>7}]"), "[  hello]");
+  }
+
+  #[test]
+  fn escapes_braces() {
+    assert_eq!(line("{{{level}}} }}{{"), "{WARN} }{");
+  }
+
+  #[test]
+  fn places_key_values() {
+    assert_eq!(line_with("{msg}", &[("a", 1), ("b", 2)]), "hello a=1 b=2");
+    assert_eq!(line_with("{kv} | {msg}", &[("a", 1)]), "a=1 | hello");
+    assert_eq!(line_with("{msg} {kv}", &[]), "hello ");
+  }
+
+  #[test]
+  fn rejects_invalid_templates() {
+    for template in ["{level", "level}", "{nope}", "{level:>x}", "{time:%Q}", "{time:%"] {
+      assert!(TextLayout::parse(template).is_err(), "`{}` was accepted", template);
+    }
+  }
+
+  #[test]
+  fn writes_json_lines() {
+    let expected = format!(
+      concat!(
+        r#"{{"timestamp":"{}","level":"WARN","target":"my_app::db","module":"my_app::db","#,
+        r#""file":"src/db.rs","line":42,"thread":null,"message":"hello"}}"#
+      ),
+      timestamp()
+    );
+    assert_eq!(structured::<i32>(LogFormat::Json, "hello", true, &[]), expected);
+  }
+
+  #[test]
+  fn writes_null_for_a_missing_location() {
+    let line = structured::<i32>(LogFormat::Json, "", false, &[]);
+    assert!(line.ends_with(r#""module":null,"file":null,"line":null,"thread":null,"message":""}"#));
+  }
+
+  #[test]
+  fn escapes_json_strings() {
+    let line = structured::<i32>(LogFormat::Json, "a \"b\" \\ c\nd\te\u{7}", true, &[]);
+    assert!(line.ends_with(r#""message":"a \"b\" \\ c\nd\te\u0007"}"#), "{}", line);
+  }
+
+  #[test]
+  fn writes_json_fields() {
+    let numbers = structured(LogFormat::Json, "hello", true, &[("id", 7), ("delta", -1)]);
+    assert!(numbers.ends_with(r#""message":"hello","fields":{"id":7,"delta":-1}}"#));
+    let strings = structured(LogFormat::Json, "hello", true, &[("user \"x\"", "a\nb")]);
+    assert!(strings.ends_with(r#""fields":{"user \"x\"":"a\nb"}}"#), "{}", strings);
+  }
+
+  #[test]
+  fn writes_logfmt_lines() {
+    let expected = format!(
+      "ts={} level=warn target=my_app::db module=my_app::db file=src/db.rs line=42 msg=hello",
+      timestamp()
+    );
+    assert_eq!(structured::<i32>(LogFormat::Logfmt, "hello", true, &[]), expected);
+    let unlocated = structured::<i32>(LogFormat::Logfmt, "hello", false, &[]);
+    assert_eq!(unlocated, format!("ts={} level=warn target=my_app::db msg=hello", timestamp()));
+  }
+
+  #[test]
+  fn quotes_logfmt_values() {
+    let line = |message| structured::<i32>(LogFormat::Logfmt, message, false, &[]);
+    assert!(line("").ends_with(" msg=\"\""));
+    assert!(line("a b").ends_with(r#" msg="a b""#));
+    assert!(line("a=b").ends_with(r#" msg="a=b""#));
+    assert!(line("say \"hi\"\\").ends_with(r#" msg="say \"hi\"\\""#));
+    assert!(line("a\nb\tc\u{7}").ends_with(r#" msg="a\nb\tc\u0007""#));
+  }
+
+  #[test]
+  fn sanitizes_logfmt_keys() {
+    let kvs = [("user name", "x"), ("a=b", "y"), ("\"q\"", ""), ("", "z")];
+    let line = structured(LogFormat::Logfmt, "hello", false, &kvs);
+    assert!(line.ends_with(r#" msg=hello user_name=x a_b=y _q_="" _=z"#), "{}", line);
+    assert_eq!(line_with("{msg}", &[("a b", 1)]), "hello a_b=1");
+  }
+}
@@ -1,2 +1,27 @@
+mod async_writer;
+mod compression;
+#[cfg(feature = "config")]
+mod config;
+mod env;
+mod error;
+mod filter;
+mod format;
 pub mod logger;
+mod naming;
+mod panic;
+mod rotating_file;
+mod shared;
+mod syslog;
+mod units;
+pub use async_writer::OverflowPolicy;
+pub use compression::Compression;
+#[cfg(feature = "config")]
+pub use config::{
+  AsyncQueueConfig, ConfigFormat, LoggerConfig, RotationConfig, SyslogConfig, SyslogTransport,
+  TargetConfig, TargetKind,
+};
+pub use format::LogFormat;
 pub use logger::*;
+pub use naming::FileNaming;
+pub use rotating_file::{RotationClock, RotationPeriod};
+pub use syslog::{Facility, Syslog, SyslogProtocol};
@@ -1,47 +1,281 @@
 use std::{
-  error::Error as StdError,
-  fs::{self, File},
-  path::PathBuf,
-  result::Result as StdResult,
+  fs,
+  io::{self, IsTerminal, Write},
+  path::{Path, PathBuf},
+  sync::{
+    atomic::{AtomicU64, Ordering},
+    mpsc, Arc, RwLock,
+  },
+  thread,
+  time::Duration,
 };
 
-use chrono::Local;
-
-use fern::{
-  colors::{Color, ColoredLevelConfig},
-  Dispatch,
-};
+use fern::{Dispatch, Output};
 
 pub use log::Level as LogLevel;
-use log::LevelFilter;
+pub use log::LevelFilter as LogLevelFilter;
 
-pub type Result<T> = StdResult<T, Box<dyn StdError>>;
+pub use crate::error::{Error, Result};
+use log::{LevelFilter, Log};
+
+use crate::{
+  async_writer::{AsyncWriter, OverflowPolicy, QueueOptions},
+  compression::Compression,
+  filter::{Directives, Levels},
+  format::{self, LogFormat, TextLayout},
+  naming::FileNaming,
+  panic,
+  rotating_file::{RotatingFile, RotationClock, RotationPeriod, RotationPolicy},
+  shared::{self, Current, SharedLog},
+  syslog::Syslog,
+};
 
 const DEFAULT_MAX_FILE_SIZE: u128 = 1024 * 1024;
+const DEFAULT_MAX_ROTATED_FILES: usize = 1;
 
 /// Targets of the logs.
+#[derive(Clone)]
 pub enum LogTarget {
   /// Log to console.
   Console,
+  /// Log to stderr.
+  Stderr,
+  /// Log to stderr from the builder's `stderr_level` up, and to stdout below.
+  SplitConsole,
   /// Log to the specified dir.
   Dir(PathBuf),
+  /// Log to the specified file, rotated files being kept next to it.
+  File(PathBuf),
+  /// Log to a syslog daemon.
+  Syslog(Syslog),
+}
+
+/// A log target together with its own options.
+#[derive(Clone)]
+pub struct Target {
+  pub(crate) target: LogTarget,
+  naming: FileNaming,
+  format: Option<LogFormat>,
+  pub(crate) level: Option<LevelFilter>,
+  pub(crate) directives: Option<String>,
+  /// Rotation options overriding the builder's, set by configs.
+  pub(crate) rotation: Option<RotationPolicy>,
+}
+
+impl From<LogTarget> for Target {
+  fn from(target: LogTarget) -> Self {
+    Self {
+      target,
+      naming: FileNaming::default(),
+      format: None,
+      level: None,
+      directives: None,
+      rotation: None,
+    }
+  }
+}
+
+impl LogTarget {
+  /// Names the files of a dir target after `naming` instead of `app.log`.
+  /// File targets take the name and the extension from their path and only
+  /// the rotated-name template from `naming`.
+  pub fn naming(self, naming: FileNaming) -> Target {
+    Target::from(self).naming(naming)
+  }
+
+  /// Formats the records of this target as `format` instead of the builder's.
+  pub fn format(self, format: LogFormat) -> Target {
+    Target::from(self).format(format)
+  }
+
+  /// Logs the records of this target from `level` up, see `Target::level`.
+  pub fn level(self, level: LogLevel) -> Target {
+    Target::from(self).level(level)
+  }
+
+  /// Sets levels per module for this target, see `Target::directives`.
+  pub fn directives<T: Into<String>>(self, directives: T) -> Target {
+    Target::from(self).directives(directives)
+  }
 }
 
+impl Target {
+  /// Names the files of a dir target after `naming` instead of `app.log`.
+  /// File targets take the name and the extension from their path and only
+  /// the rotated-name template from `naming`.
+  pub fn naming(mut self, naming: FileNaming) -> Self {
+    self.naming = naming;
+    self
+  }
+
+  /// Formats the records of this target as `format` instead of the builder's.
+  pub fn format(mut self, format: LogFormat) -> Self {
+    self.format = Some(format);
+    self
+  }
+
+  /// Logs the records of this target from `level` up, whatever the builder's
+  /// level and directives. Runtime level changes don't apply to it.
+  pub fn level(mut self, level: LogLevel) -> Self {
+    self.level = Some(level.to_level_filter());
+    self
+  }
+
+  /// Sets levels per module for this target like the builder's `directives`,
+  /// on top of its own level or else the builder's, and ignoring the
+  /// builder's directives. Runtime level changes don't apply to it.
+  pub fn directives<T: Into<String>>(mut self, directives: T) -> Self {
+    self.directives = Some(directives.into());
+    self
+  }
+
+  /// Returns the levels of this target if it has its own.
+  fn own_levels(&self, default_level: LevelFilter) -> Result<Option<Directives>> {
+    if self.level.is_none() && self.directives.is_none() {
+      return Ok(None);
+    }
+    let level = self.level.unwrap_or(default_level);
+    let directives = self.directives.as_deref().unwrap_or_default();
+    Ok(Some(Directives::parse(level, directives).map_err(Error::InvalidConfig)?))
+  }
+
+  /// Returns the naming of the files of this target if it writes to files.
+  fn file_naming(&self) -> Option<FileNaming> {
+    match &self.target {
+      LogTarget::Dir(_) => Some(self.naming.clone()),
+      LogTarget::File(path) => Some(self.naming.for_file(path)),
+      _ => None,
+    }
+  }
+}
+
+/// A handle on the installed logger, which can be cloned and shared between
+/// threads.
+///
+/// It also guards the outputs: once its last clone is dropped, the pending
+/// records are written and every output flushed, so it should be kept until
+/// the end of `main`. Logging goes on afterwards.
+#[derive(Clone)]
 pub struct Logger {
-  level: LevelFilter,
-  max_file_size: u128,
-  targets: Vec<LogTarget>,
+  pub(crate) shared: Arc<SharedLog>,
+  _guard: Arc<FlushGuard>,
+}
+
+/// Flushes the outputs of a logger when dropped.
+struct FlushGuard(Arc<SharedLog>);
+
+impl Drop for FlushGuard {
+  fn drop(&mut self) {
+    self.0.flush();
+  }
 }
 
+#[derive(Clone)]
 pub struct LoggerBuilder {
-  level: LevelFilter,
-  max_file_size: u128,
-  targets: Vec<LogTarget>,
+  pub(crate) level: LevelFilter,
+  pub(crate) directives: String,
+  pub(crate) format: LogFormat,
+  pub(crate) format_template: String,
+  pub(crate) stderr_level: LogLevel,
+  pub(crate) rotation: RotationPolicy,
+  pub(crate) queue: Option<QueueOptions>,
+  pub(crate) log_panics: bool,
+  pub(crate) targets: Vec<Target>,
+}
+
+impl Logger {
+  pub(crate) fn new(shared: Arc<SharedLog>) -> Self {
+    let guard = Arc::new(FlushGuard(Arc::clone(&shared)));
+    Self { shared, _guard: guard }
+  }
+
+  /// Writes the pending records, flushes and closes every output, waiting
+  /// for at most `timeout`. Returns whether it was all done in time. Records
+  /// logged afterwards are discarded, and the logger is never reloaded.
+  pub fn shutdown(&self, timeout: Duration) -> bool {
+    let log = self.shared.take();
+    let (done, finished) = mpsc::channel();
+    let closing = thread::Builder::new().name("yaslog-shutdown".to_string()).spawn(move || {
+      log.flush();
+      drop(log);
+      let _ = done.send(());
+    });
+    match closing {
+      Ok(_) => finished.recv_timeout(timeout).is_ok(),
+      // The outputs were closed along with the thread's closure.
+      Err(_) => true,
+    }
+  }
+
+  /// Replaces the configuration of the logger by the one of `builder`, all at
+  /// once. The new outputs are opened while records keep going to the current
+  /// ones, files still open being shared rather than opened twice. The
+  /// current configuration is kept if `builder` fails to build.
+  pub fn reload(&self, builder: LoggerBuilder) -> Result<()> {
+    self.shared.reload(builder)
+  }
+
+  /// Returns the default level, the one of the records of modules without
+  /// their own.
+  pub fn level(&self) -> LogLevelFilter {
+    self.shared.read_levels(|levels| levels.default.level())
+  }
+
+  /// Sets the default level, taking effect on every thread at once.
+  pub fn set_level(&self, level: LogLevelFilter) {
+    self.shared.update_levels(|levels| levels.default.set_level(level));
+  }
+
+  /// Returns the level of the records of `module`, its own or inherited.
+  pub fn module_level(&self, module: &str) -> LogLevelFilter {
+    self.shared.read_levels(|levels| levels.default.level_for(module))
+  }
+
+  /// Sets the level of `module` and its submodules without their own, like a
+  /// `module=level` directive.
+  pub fn set_module_level(&self, module: &str, level: LogLevelFilter) {
+    self.shared.update_levels(|levels| levels.default.set(module, level));
+  }
+
+  /// Returns how many records the asynchronous outputs dropped because
+  /// their queue was full.
+  pub fn dropped_records(&self) -> u64 {
+    self.shared.dropped.load(Ordering::Relaxed)
+  }
+
+  /// Removes the level set for `module`, which inherits it again.
+  pub fn reset_module_level(&self, module: &str) {
+    self.shared.update_levels(|levels| levels.default.remove(module));
+  }
+}
+
+impl Default for LoggerBuilder {
+  fn default() -> Self {
+    Self::new()
+  }
 }
 
 impl LoggerBuilder {
   pub fn new() -> Self {
-    Self { level: LevelFilter::Trace, max_file_size: DEFAULT_MAX_FILE_SIZE, targets: Vec::new() }
+    Self {
+      level: LevelFilter::Trace,
+      directives: String::new(),
+      format: LogFormat::Text,
+      format_template: format::DEFAULT_TEMPLATE.to_string(),
+      stderr_level: LogLevel::Warn,
+      rotation: RotationPolicy {
+        max_file_size: DEFAULT_MAX_FILE_SIZE,
+        max_rotated_files: DEFAULT_MAX_ROTATED_FILES,
+        period: None,
+        clock: RotationClock::Local,
+        compression: None,
+        max_total_size: None,
+        max_age: None,
+      },
+      queue: None,
+      log_panics: false,
+      targets: Vec::new(),
+    }
   }
 
   pub fn level(mut self, level: LogLevel) -> Self {
@@ -49,86 +283,284 @@ impl LoggerBuilder {
     self
   }
 
+  /// Sets levels per module with directives like
+  /// `"info,my_app::db=trace,hyper=warn"`, matched against the targets of the
+  /// records. A module's level covers its submodules unless they have their
+  /// own, a bare level replaces the one set by `level`, and a bare module
+  /// enables all of its records. The directives are checked by `build`.
+  pub fn directives<T: Into<String>>(mut self, directives: T) -> Self {
+    self.directives = directives.into();
+    self
+  }
+
+  /// Sets the format of the records, text by default. Targets may override it.
+  pub fn format(mut self, format: LogFormat) -> Self {
+    self.format = format;
+    self
+  }
+
+  /// Lays out the text records after `template`, e.g.
+  /// `"{time:%H:%M:%S%.3f} {level:>5} {module}::{line} - {msg}"`.
+  ///
+  /// The fields are `time`, `level`, `target`, `module`, `file`, `line`,
+  /// `thread`, `thread_id`, `pid`, `hostname`, `msg` and `kv`, the record's
+  /// key-values, which are appended to the line if left out. Each can be padded
+  /// and aligned like `{level:<5}`, `{level:>5}` or `{level:^5}`, except
+  /// `time`, which takes a strftime format instead. `{{` and `}}` stand for
+  /// literal braces. The template is checked by `build`.
+  pub fn format_template<T: Into<String>>(mut self, template: T) -> Self {
+    self.format_template = template.into();
+    self
+  }
+
+  /// Sets the least severe level a split console sends to stderr, `Warn` by
+  /// default.
+  pub fn stderr_level(mut self, stderr_level: LogLevel) -> Self {
+    self.stderr_level = stderr_level;
+    self
+  }
+
   pub fn max_file_size(mut self, max_file_size: u128) -> Self {
-    self.max_file_size = max_file_size;
+    self.rotation.max_file_size = max_file_size;
     self
   }
 
-  pub fn targets<T: IntoIterator<Item = LogTarget>>(mut self, targets: T) -> Self {
+  /// Sets how many rotated files (`app.log.1` ... `app.log.N`) are kept.
+  pub fn max_rotated_files(mut self, max_rotated_files: usize) -> Self {
+    self.rotation.max_rotated_files = max_rotated_files;
+    self
+  }
+
+  /// Rolls the log file over at the end of every `period` too, naming the
+  /// rotated files after their period, e.g. `app.2026-10-18.log`.
+  pub fn rotation_period(mut self, period: RotationPeriod) -> Self {
+    self.rotation.period = Some(period);
+    self
+  }
+
+  /// Sets whether rotation periods follow local time (the default) or UTC.
+  pub fn rotation_clock(mut self, clock: RotationClock) -> Self {
+    self.rotation.clock = clock;
+    self
+  }
+
+  /// Compresses rotated files on a background thread.
+  pub fn compression(mut self, compression: Compression) -> Self {
+    self.rotation.compression = Some(compression);
+    self
+  }
+
+  /// Deletes the oldest rotated files once they take more than
+  /// `max_total_size` bytes in total.
+  pub fn max_total_size(mut self, max_total_size: u128) -> Self {
+    self.rotation.max_total_size = Some(max_total_size);
+    self
+  }
+
+  /// Deletes rotated files which were last written more than `max_age` ago.
+  pub fn max_age(mut self, max_age: Duration) -> Self {
+    self.rotation.max_age = Some(max_age);
+    self
+  }
+
+  /// Writes the records on a background thread per output, handing them
+  /// over through a queue of `capacity` records once formatted, so that slow
+  /// outputs don't hold up the logging threads. The records being written
+  /// count against the capacity until they are. `overflow` tells what to do
+  /// when a queue is full, see `Logger::dropped_records`.
+  pub fn async_queue(mut self, capacity: usize, overflow: OverflowPolicy) -> Self {
+    self.queue = Some(QueueOptions { capacity, overflow });
+    self
+  }
+
+  /// Logs panics at `Error` level, with their location, thread and a
+  /// backtrace, before handing them to the previous panic hook. Only applies
+  /// to loggers installed by `build`.
+  pub fn log_panics(mut self, log_panics: bool) -> Self {
+    self.log_panics = log_panics;
+    self
+  }
+
+  /// Adds a target, a `LogTarget` or a `Target` with its own options.
+  pub fn target<T: Into<Target>>(mut self, target: T) -> Self {
+    self.targets.push(target.into());
+    self
+  }
+
+  /// Adds targets of one type. Plain `LogTarget`s and ones with options,
+  /// which are `Target`s, are mixed by converting the plain ones with
+  /// `Target::from` or `.into()`, or by adding them one by one with `target`.
+  pub fn targets<T: IntoIterator<Item = I>, I: Into<Target>>(mut self, targets: T) -> Self {
     for target in targets {
-      self.targets.push(target);
+      self.targets.push(target.into());
     }
     self
   }
 
+  /// Builds the logger and installs it as the global one.
   pub fn build(self) -> Result<Logger> {
-    let logger =
-      Logger { level: self.level, max_file_size: self.max_file_size, targets: self.targets };
-    Self::apply(&logger)?;
+    let log_panics = self.log_panics;
+    let logger = self.build_shared(true)?;
+    log::set_boxed_logger(Box::new(Arc::clone(&logger.shared)))?;
+    log::set_max_level(logger.shared.read_levels(Levels::max_level));
+    if log_panics {
+      panic::install_hook();
+    }
     Ok(logger)
   }
 
-  fn apply(logger: &Logger) -> Result<()> {
-    let colors = ColoredLevelConfig::new()
-      .info(Color::BrightBlue)
-      .warn(Color::BrightYellow)
-      .error(Color::BrightRed);
-    let mut dispatch = Dispatch::new()
-      .format(move |out, message, record| {
-        let line = match record.line() {
-          Some(line) => line,
-          None => 0,
-        };
-        out.finish(format_args!(
-          "[{}]<{}>[{}:{}] {}",
-          Local::now().format("%Y-%m-%d %H:%M:%S"),
-          colors.color(record.level()),
-          record.target(),
-          line,
-          message
-        ))
-      })
-      .level(logger.level);
-
-    for target in &logger.targets {
-      dispatch = match target {
-        LogTarget::Console => dispatch.chain(std::io::stdout()),
+  /// Builds the logger without installing it, for tests, composite loggers
+  /// or libraries embedding yaslog. Returns the most verbose level it logs,
+  /// which callers installing it should pass to `log::set_max_level`, and the
+  /// logger.
+  pub fn build_boxed(self) -> Result<(LogLevelFilter, Box<dyn Log>)> {
+    let logger = self.build_shared(false)?;
+    let max_level = logger.shared.read_levels(Levels::max_level);
+    Ok((max_level, Box::new(logger.shared)))
+  }
+
+  fn build_shared(self, global: bool) -> Result<Logger> {
+    let dropped = Arc::new(AtomicU64::new(0));
+    let current = self.open(&dropped)?;
+    Ok(Logger::new(Arc::new(SharedLog::new(current, dropped, global))))
+  }
+
+  /// Opens the outputs and returns the dispatch to them along with the
+  /// levels, which the shared logger checks first.
+  pub(crate) fn open(&self, dropped: &Arc<AtomicU64>) -> Result<Current> {
+    let placeholder = Levels { default: Directives::new(LevelFilter::Off), targets: Vec::new() };
+    let shared_levels = Arc::new(RwLock::new(placeholder));
+    let (levels, dispatch) = self.dispatch(&shared_levels, dropped)?;
+    *shared::write(&shared_levels) = levels;
+    Ok(Current { log: dispatch.into_log().1, levels: shared_levels })
+  }
+
+  /// Returns the levels, which the shared logger checks first, and the
+  /// dispatch to the targets. Targets following the default levels read them
+  /// from `shared_levels` if others have their own.
+  fn dispatch(
+    &self, shared_levels: &Arc<RwLock<Levels>>, dropped: &Arc<AtomicU64>,
+  ) -> Result<(Levels, Dispatch)> {
+    let layout = Arc::new(TextLayout::parse(&self.format_template).map_err(Error::InvalidConfig)?);
+    let default = Directives::parse(self.level, &self.directives).map_err(Error::InvalidConfig)?;
+    let mut levels = Levels { default, targets: Vec::new() };
+    let own_levels = self
+      .targets
+      .iter()
+      .map(|target| target.own_levels(self.level))
+      .collect::<Result<Vec<_>>>()?;
+    let filtered = own_levels.iter().any(Option::is_some);
+    for target in &self.targets {
+      if let Some(naming) = target.file_naming() {
+        let period = target.rotation.as_ref().unwrap_or(&self.rotation).period;
+        naming.template(period.is_some()).map_err(Error::InvalidConfig)?;
+      }
+    }
+
+    // The files are opened last, as a file still open in a previous dispatch
+    // takes on the new naming and rotation when it is opened again.
+    let mut order = (0..self.targets.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&index| self.targets[index].file_naming().is_some());
+    let mut outputs = (0..self.targets.len()).map(|_| None).collect::<Vec<_>>();
+    for index in order {
+      let target = &self.targets[index];
+      let format = target.format.unwrap_or(self.format);
+      let rotation = target.rotation.as_ref().unwrap_or(&self.rotation);
+      outputs[index] = Some(match &target.target {
+        LogTarget::Console => self.console(io::stdout(), format, &layout, dropped)?,
+        LogTarget::Stderr => self.console(io::stderr(), format, &layout, dropped)?,
+        LogTarget::SplitConsole => {
+          let stderr_level = self.stderr_level;
+          Dispatch::new()
+            .chain(
+              self
+                .console(io::stderr(), format, &layout, dropped)?
+                .filter(move |metadata| metadata.level() <= stderr_level),
+            )
+            .chain(
+              self
+                .console(io::stdout(), format, &layout, dropped)?
+                .filter(move |metadata| metadata.level() > stderr_level),
+            )
+        }
         LogTarget::Dir(dir) => {
-          if !dir.exists() {
-            fs::create_dir_all(&dir).unwrap();
-          }
-          let path = Self::get_log_path(dir);
-          Self::rotate_file(dir, logger.max_file_size)?;
-          dispatch.chain(fern::log_file(path)?)
+          self.file(dir, &target.naming, rotation.clone(), format, &layout, dropped)?
         }
-      };
+        LogTarget::File(path) => {
+          let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+          };
+          let naming = target.naming.for_file(path);
+          self.file(dir, &naming, rotation.clone(), format, &layout, dropped)?
+        }
+        LogTarget::Syslog(syslog) => self.syslog(syslog, format, &layout, dropped)?,
+      });
     }
 
-    dispatch.apply()?;
+    let mut dispatch = Dispatch::new();
+    for (output, own_levels) in outputs.into_iter().flatten().zip(own_levels) {
+      dispatch = dispatch.chain(match own_levels {
+        Some(own_levels) => {
+          levels.targets.push(own_levels.clone());
+          output.filter(move |metadata| own_levels.enabled(metadata))
+        }
+        None if filtered => {
+          let shared_levels = Arc::clone(shared_levels);
+          output.filter(move |metadata| shared::read(&shared_levels).default.enabled(metadata))
+        }
+        None => output,
+      });
+    }
 
-    Ok(())
+    Ok((levels, dispatch))
   }
 
-  fn rotate_file(dir: &PathBuf, max_file_size: u128) -> Result<()> {
-    let path = Self::get_log_path(dir);
-    if path.exists() {
-      let log_size = File::open(&path)?.metadata()?.len() as u128;
-      if log_size > max_file_size {
-        let old_path = Self::get_old_log_path(dir);
-        if old_path.exists() {
-          fs::remove_file(&old_path)?;
-        }
-        fs::rename(&path, &old_path)?;
-      }
+  /// Returns the output for a console stream, colored if it is a terminal.
+  fn console<T: IsTerminal + Into<Output> + Write + Send + 'static>(
+    &self, stream: T, format: LogFormat, layout: &Arc<TextLayout>, dropped: &Arc<AtomicU64>,
+  ) -> Result<Dispatch> {
+    let colored = format::use_colors(&stream);
+    let dispatch = Dispatch::new().format(format::formatter(format, layout, colored));
+    Ok(match self.queue {
+      Some(queue) => dispatch.chain(Self::queued(Box::new(stream), queue, dropped)?),
+      None => dispatch.chain(stream),
+    })
+  }
+
+  /// Returns the output for a rotating file, which is never colored.
+  fn file(
+    &self, dir: &Path, naming: &FileNaming, rotation: RotationPolicy, format: LogFormat,
+    layout: &Arc<TextLayout>, dropped: &Arc<AtomicU64>,
+  ) -> Result<Dispatch> {
+    fs::create_dir_all(dir)
+      .map_err(|source| Error::CreateDir { path: dir.to_path_buf(), source })?;
+    let mut file: Box<dyn Write + Send> =
+      Box::new(RotatingFile::open_shared(dir, naming, rotation)?);
+    if let Some(queue) = self.queue {
+      file = Self::queued(file, queue, dropped)?;
     }
-    Ok(())
+    Ok(Dispatch::new().format(format::formatter(format, layout, false)).chain(file))
   }
 
-  fn get_log_path(dir: &PathBuf) -> PathBuf {
-    dir.join("app.log")
+  /// Returns the output for a syslog daemon, which is never colored.
+  fn syslog(
+    &self, syslog: &Syslog, format: LogFormat, layout: &Arc<TextLayout>, dropped: &Arc<AtomicU64>,
+  ) -> Result<Dispatch> {
+    let (header, writer) = syslog.open()?;
+    let mut writer: Box<dyn Write + Send> = Box::new(writer);
+    if let Some(queue) = self.queue {
+      writer = Self::queued(writer, queue, dropped)?;
+    }
+    let formatter =
+      format::prefixed_formatter(format, layout, false, move |f, record| header.write(f, record));
+    Ok(Dispatch::new().format(formatter).chain(writer))
   }
 
-  fn get_old_log_path(dir: &PathBuf) -> PathBuf {
-    dir.join("app.log.old")
+  /// Returns an output handing the records to a thread writing to `output`.
+  fn queued(
+    output: Box<dyn Write + Send>, queue: QueueOptions, dropped: &Arc<AtomicU64>,
+  ) -> Result<Box<dyn Write + Send>> {
+    Ok(Box::new(AsyncWriter::spawn(output, queue, Arc::clone(dropped))?))
   }
 }
@@ -0,0 +1,298 @@
+use std::{path::Path, process};
+
+use chrono::NaiveDateTime;
+
+/// Names of the files written by a file target.
+///
+/// The active file is `{name}.{ext}`, `app.log` by default. Rotated files are
+/// named after a template which may use these placeholders:
+///
+/// - `{name}` and `{ext}`: the base name and the extension.
+/// - `{timestamp}`: the start of the rotation period, e.g. `2026-10-18` for
+///   daily rotation, or the time of the rotation if there is no period.
+/// - `{index}`: without `{timestamp}`, the generation of the file, from 1 for
+///   the newest; with it, a number telling apart files of the same timestamp.
+/// - `{pid}`: the id of the process which rotated the file.
+///
+/// The default template is `{name}.{ext}.{index}` without a rotation period
+/// and `{name}.{timestamp}.{ext}` with one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileNaming {
+  name: String,
+  extension: String,
+  rotated: Option<String>,
+}
+
+impl Default for FileNaming {
+  fn default() -> Self {
+    Self::new("app")
+  }
+}
+
+impl FileNaming {
+  /// Creates a naming for `{name}.log`.
+  pub fn new<T: Into<String>>(name: T) -> Self {
+    Self { name: name.into(), extension: "log".to_string(), rotated: None }
+  }
+
+  /// Sets the extension, without the leading dot. An empty extension leaves
+  /// the files without one.
+  pub fn extension<T: Into<String>>(mut self, extension: T) -> Self {
+    self.extension = extension.into();
+    self
+  }
+
+  /// Sets the template of the rotated file names, e.g.
+  /// `{name}.{timestamp}.{ext}`.
+  pub fn rotated<T: Into<String>>(mut self, template: T) -> Self {
+    self.rotated = Some(template.into());
+    self
+  }
+
+  /// Returns this naming with the name and the extension of `path`.
+  pub(crate) fn for_file(&self, path: &Path) -> Self {
+    let name = path.file_stem().unwrap_or(path.as_os_str()).to_string_lossy().into_owned();
+    let extension = path.extension().unwrap_or_default().to_string_lossy().into_owned();
+    Self { name, extension, rotated: self.rotated.clone() }
+  }
+
+  pub(crate) fn file_name(&self) -> String {
+    if self.extension.is_empty() {
+      self.name.clone()
+    } else {
+      format!("{}.{}", self.name, self.extension)
+    }
+  }
+
+  /// Parses the rotated-name template, or the default one for rotation with
+  /// or without a period.
+  pub(crate) fn template(&self, periodic: bool) -> Result<Template, String> {
+    let template = match &self.rotated {
+      Some(template) => template.as_str(),
+      None if periodic => "{name}.{timestamp}.{ext}",
+      None => "{name}.{ext}.{index}",
+    };
+
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+      literal.push_str(&rest[..open]);
+      let close = match rest[open..].find('}') {
+        Some(close) => open + close,
+        None => return Err(format!("unclosed placeholder in `{}`", template)),
+      };
+      match &rest[open + 1..close] {
+        "name" => literal.push_str(&self.name),
+        "ext" if self.extension.is_empty() => {
+          if literal.ends_with('.') {
+            literal.pop();
+          }
+        }
+        "ext" => literal.push_str(&self.extension),
+        placeholder => {
+          let part = match placeholder {
+            "index" => Part::Index,
+            "timestamp" => Part::Timestamp,
+            "pid" => Part::Pid,
+            _ => {
+              return Err(format!("unknown placeholder `{{{}}}` in `{}`", placeholder, template))
+            }
+          };
+          if !literal.is_empty() {
+            parts.push(Part::Literal(literal.split_off(0)));
+          }
+          parts.push(part);
+        }
+      }
+      rest = &rest[close + 1..];
+    }
+    literal.push_str(rest);
+    if !literal.is_empty() {
+      parts.push(Part::Literal(literal));
+    }
+
+    let parsed = Template { parts };
+    if !parsed.is_indexed() && !parsed.is_timestamped() {
+      return Err(format!("`{}` needs an `{{index}}` or a `{{timestamp}}`", template));
+    }
+    Ok(parsed)
+  }
+}
+
+/// A parsed rotated-name template.
+#[derive(Clone, Debug)]
+pub(crate) struct Template {
+  parts: Vec<Part>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Part {
+  Literal(String),
+  Index,
+  Timestamp,
+  Pid,
+}
+
+impl Template {
+  pub(crate) fn is_timestamped(&self) -> bool {
+    self.has(&Part::Timestamp)
+  }
+
+  pub(crate) fn is_indexed(&self) -> bool {
+    self.has(&Part::Index)
+  }
+
+  fn has(&self, part: &Part) -> bool {
+    self.parts.contains(part)
+  }
+
+  pub(crate) fn render(&self, index: usize, timestamp: &str) -> String {
+    let mut name = String::new();
+    for part in &self.parts {
+      match part {
+        Part::Literal(literal) => name.push_str(literal),
+        Part::Index => name.push_str(&index.to_string()),
+        Part::Timestamp => name.push_str(timestamp),
+        Part::Pid => name.push_str(&process::id().to_string()),
+      }
+    }
+    name
+  }
+
+  /// Parses `name` back into the generation it was rendered for, if this
+  /// template with timestamps formatted by `timestamp_format` could have
+  /// produced it. Timestamps of templates without `{index}` may carry a `.N`
+  /// suffix telling apart their files, which is taken as the index.
+  pub(crate) fn parse(&self, name: &str, timestamp_format: &str) -> Option<Generation> {
+    let shape = shape(&NaiveDateTime::default().format(timestamp_format).to_string());
+    self.parse_parts(&self.parts, name, &shape, Generation::default())
+  }
+
+  fn parse_parts(
+    &self, parts: &[Part], name: &str, shape_of_timestamp: &str, found: Generation,
+  ) -> Option<Generation> {
+    let parse_rest = |name: &str, found: Generation| {
+      self.parse_parts(&parts[1..], name, shape_of_timestamp, found)
+    };
+    let parse_after_digits = |name: &str, found: &Generation, is_index: bool| {
+      let digits = name.bytes().take_while(u8::is_ascii_digit).count();
+      (1..=digits).find_map(|len| {
+        let mut found = found.clone();
+        if is_index {
+          found.index = name[..len].parse().ok()?;
+        }
+        parse_rest(&name[len..], found)
+      })
+    };
+    match parts.first() {
+      None => name.is_empty().then_some(found),
+      Some(Part::Literal(literal)) => parse_rest(name.strip_prefix(literal.as_str())?, found),
+      Some(Part::Index) => parse_after_digits(name, &found, true),
+      Some(Part::Pid) => parse_after_digits(name, &found, false),
+      Some(Part::Timestamp) => {
+        let len = shape_of_timestamp.len();
+        if !name.is_char_boundary(len) || shape(&name[..len]) != shape_of_timestamp {
+          return None;
+        }
+        let found = Generation { timestamp: name[..len].to_string(), ..found };
+        let name = &name[len..];
+        parse_rest(name, found.clone()).or_else(|| {
+          let suffixed = name.strip_prefix('.').filter(|_| !self.is_indexed())?;
+          let digits = suffixed.bytes().take_while(u8::is_ascii_digit).count();
+          (1..=digits).find_map(|len| {
+            let index = suffixed[..len].parse().ok()?;
+            parse_rest(&suffixed[len..], Generation { index, ..found.clone() })
+          })
+        })
+      }
+    }
+  }
+}
+
+/// What a rotated file name tells of its age: its timestamp, empty without
+/// one, and its index, 0 for a timestamp without a `.N` suffix.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Generation {
+  pub(crate) timestamp: String,
+  pub(crate) index: usize,
+}
+
+/// Replaces every digit by `0`, so timestamps of the same format compare equal.
+fn shape(text: &str) -> String {
+  text.chars().map(|c| if c.is_ascii_digit() { '0' } else { c }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const DAILY: &str = "%Y-%m-%d";
+
+  fn generation(timestamp: &str, index: usize) -> Option<Generation> {
+    Some(Generation { timestamp: timestamp.to_string(), index })
+  }
+
+  #[test]
+  fn default_indexed_template() {
+    let template = FileNaming::default().template(false).unwrap();
+    assert_eq!(template.render(3, ""), "app.log.3");
+    assert_eq!(template.parse("app.log.1", DAILY), generation("", 1));
+    assert_eq!(template.parse("app.log.12", DAILY), generation("", 12));
+    assert_eq!(template.parse("app.log", DAILY), None);
+    assert_eq!(template.parse("app.log.", DAILY), None);
+    assert_eq!(template.parse("app.log.1x", DAILY), None);
+    assert_eq!(template.parse("app.log.rotating", DAILY), None);
+    assert_eq!(template.parse("other.log.1", DAILY), None);
+  }
+
+  #[test]
+  fn default_timestamped_template() {
+    let template = FileNaming::default().template(true).unwrap();
+    assert_eq!(template.render(0, "2026-10-18"), "app.2026-10-18.log");
+    assert_eq!(template.parse("app.2026-10-18.log", DAILY), generation("2026-10-18", 0));
+    assert_eq!(template.parse("app.2026-10-18.2.log", DAILY), generation("2026-10-18", 2));
+    assert_eq!(template.parse("app.2026-10-18.log", "%Y-%m-%dT%H"), None);
+    assert_eq!(template.parse("app.2026-10.log", DAILY), None);
+    assert_eq!(template.parse("app.2026-10-18.x.log", DAILY), None);
+    assert_eq!(template.parse("app.log", DAILY), None);
+  }
+
+  #[test]
+  fn timestamped_and_indexed_template() {
+    let naming = FileNaming::default().rotated("{name}.{timestamp}.{index}.{ext}");
+    let template = naming.template(true).unwrap();
+    assert_eq!(template.parse("app.2026-10-18.3.log", DAILY), generation("2026-10-18", 3));
+    assert_eq!(template.parse("app.2026-10-18.log", DAILY), None);
+  }
+
+  #[test]
+  fn pid_template() {
+    let template =
+      FileNaming::default().rotated("{name}-{pid}.{ext}.{index}").template(false).unwrap();
+    assert_eq!(template.render(1, ""), format!("app-{}.log.1", process::id()));
+    assert_eq!(template.parse("app-4242.log.2", DAILY), generation("", 2));
+    assert_eq!(template.parse("app-.log.2", DAILY), None);
+    assert_eq!(template.parse("app-42a.log.2", DAILY), None);
+  }
+
+  #[test]
+  fn template_without_extension() {
+    let naming = FileNaming::new("app").extension("");
+    assert_eq!(naming.file_name(), "app");
+    let indexed = naming.template(false).unwrap();
+    assert_eq!(indexed.render(1, ""), "app.1");
+    assert_eq!(indexed.parse("app.1", DAILY), generation("", 1));
+    assert_eq!(indexed.parse("app.log.1", DAILY), None);
+    let timestamped = naming.template(true).unwrap();
+    assert_eq!(timestamped.render(0, "2026-10-18"), "app.2026-10-18");
+    assert_eq!(timestamped.parse("app.2026-10-18.1", DAILY), generation("2026-10-18", 1));
+  }
+
+  #[test]
+  fn invalid_templates() {
+    assert!(FileNaming::default().rotated("{name}.{ext}").template(false).is_err());
+    assert!(FileNaming::default().rotated("{name}.{index").template(false).is_err());
+    assert!(FileNaming::default().rotated("{name}.{date}").template(false).is_err());
+  }
+}
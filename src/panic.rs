@@ -0,0 +1,40 @@
+use std::{backtrace::Backtrace, panic, thread};
+
+use crate::{async_writer, shared};
+
+/// Installs a panic hook logging the panics at `Error` level with their
+/// location, thread and backtrace, then calling the previous hook.
+///
+/// Panics raised inside a logger, e.g. by a `Display` impl, or on the thread
+/// of an asynchronous output are only passed to the previous hook, as logging
+/// them would wait for locks or a queue held by the panicking thread.
+pub(crate) fn install_hook() {
+  let previous = panic::take_hook();
+  panic::set_hook(Box::new(move |info| {
+    let thread = thread::current();
+    if shared::is_logging() || thread.name() == Some(async_writer::THREAD_NAME) {
+      return previous(info);
+    }
+    let payload = info.payload();
+    let message = match payload.downcast_ref::<&str>() {
+      Some(message) => message,
+      None => payload.downcast_ref::<String>().map_or("Box<dyn Any>", String::as_str),
+    };
+    let thread = thread.name().unwrap_or("<unnamed>");
+    let location = match info.location() {
+      Some(location) => format!("{}:{}:{}", location.file(), location.line(), location.column()),
+      None => "an unknown location".to_string(),
+    };
+    let backtrace = Backtrace::force_capture();
+    log::error!(
+      target: "panic",
+      "thread '{}' panicked at {}: {}\nstack backtrace:\n{}",
+      thread,
+      location,
+      message,
+      backtrace
+    );
+    log::logger().flush();
+    previous(info);
+  }));
+}
@@ -0,0 +1,869 @@
+use std::{
+  collections::HashMap,
+  ffi::OsString,
+  fs::{self, File, OpenOptions},
+  io::{self, BufWriter, Write},
+  iter, mem,
+  path::{Path, PathBuf},
+  str::FromStr,
+  sync::{
+    mpsc::{self, SendError, Sender},
+    Arc, Mutex, MutexGuard, Weak,
+  },
+  thread::{self, JoinHandle},
+  time::{Duration, SystemTime},
+};
+
+use chrono::{DateTime, Datelike, Local, NaiveDateTime, NaiveTime, TimeDelta, Timelike, Utc};
+
+use crate::{
+  compression::{Compression, COMPRESSED_EXTENSIONS},
+  error::{self, Error},
+  naming::{FileNaming, Generation, Template},
+  units,
+};
+
+/// Timestamp of the rotated files when there is no rotation period.
+const DEFAULT_TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H-%M-%S";
+
+/// The files open in this process by path, so that a file reopened by a
+/// reload, or named by two targets, is written by a single `RotatingFile`.
+static OPEN_FILES: Mutex<Vec<(PathBuf, Weak<Mutex<RotatingFile>>)>> = Mutex::new(Vec::new());
+
+/// Periods at which the log file is rolled over, in addition to `max_file_size`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RotationPeriod {
+  /// Roll over at the start of every hour.
+  Hourly,
+  /// Roll over at midnight.
+  Daily,
+  /// Roll over at midnight between Sunday and Monday.
+  Weekly,
+  /// Roll over every interval, counted from midnight of 1970-01-01.
+  Interval(Duration),
+}
+
+/// Clocks used to find the rotation boundaries.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RotationClock {
+  #[default]
+  Local,
+  Utc,
+}
+
+impl FromStr for RotationPeriod {
+  type Err = String;
+
+  /// Parses `hourly`, `daily`, `weekly`, ignoring case, or an interval like
+  /// `30m` or `12h`.
+  fn from_str(period: &str) -> Result<Self, String> {
+    match period.to_ascii_lowercase().as_str() {
+      "hourly" => Ok(RotationPeriod::Hourly),
+      "daily" => Ok(RotationPeriod::Daily),
+      "weekly" => Ok(RotationPeriod::Weekly),
+      _ => match units::parse_duration(period) {
+        Ok(interval) if !interval.is_zero() => Ok(RotationPeriod::Interval(interval)),
+        _ => Err(format!("unknown rotation period `{}`", period)),
+      },
+    }
+  }
+}
+
+impl FromStr for RotationClock {
+  type Err = String;
+
+  /// Parses `local` or `utc`, ignoring case.
+  fn from_str(clock: &str) -> Result<Self, String> {
+    match clock.to_ascii_lowercase().as_str() {
+      "local" => Ok(RotationClock::Local),
+      "utc" => Ok(RotationClock::Utc),
+      _ => Err(format!("unknown rotation clock `{}`", clock)),
+    }
+  }
+}
+
+#[derive(Clone)]
+pub(crate) struct RotationPolicy {
+  pub(crate) max_file_size: u128,
+  pub(crate) max_rotated_files: usize,
+  pub(crate) period: Option<RotationPeriod>,
+  pub(crate) clock: RotationClock,
+  pub(crate) compression: Option<Compression>,
+  pub(crate) max_total_size: Option<u128>,
+  pub(crate) max_age: Option<Duration>,
+}
+
+/// A log file which rolls itself over once it grows past `max_file_size` or
+/// its rotation period ends.
+///
+/// The size is checked whenever the writer is flushed and the period whenever
+/// a new record starts. Fern writes a whole record and flushes the writer
+/// while holding its lock, so a record is never split across two files and
+/// rotation never interleaves with other records.
+///
+/// Rotated files are named after the target's `FileNaming`. Without a
+/// timestamp in their names, they are kept as `app.log.1` (the newest) up to
+/// `app.log.N` (the oldest), where `N` is `max_rotated_files`. With one, they
+/// are named after the period they cover, e.g. `app.2026-10-18.log`, and a
+/// period which is rotated more than once because of its size continues as
+/// `app.2026-10-18.1.log`, `app.2026-10-18.2.log`, and so on.
+///
+/// With compression, rotated files are handed to a background worker which
+/// names, compresses and cleans them up in order, so the writer never waits
+/// for the compression.
+pub(crate) struct RotatingFile {
+  roller: Roller,
+  file: BufWriter<File>,
+  size: u128,
+  period_start: Option<NaiveDateTime>,
+  in_record: bool,
+  worker: Option<Sender<Staged>>,
+  worker_thread: Option<JoinHandle<()>>,
+}
+
+/// Where a log file lives, how its rotated files are named and how many of
+/// them are kept.
+#[derive(Clone)]
+struct Roller {
+  path: PathBuf,
+  template: Template,
+  policy: RotationPolicy,
+}
+
+/// A rotated file found in the directory.
+struct RotatedFile {
+  path: PathBuf,
+  modified: SystemTime,
+  size: u128,
+  generation: Generation,
+}
+
+/// A rotated file waiting for the worker under its staging name, rolled as
+/// its roller was when it was staged.
+struct Staged {
+  path: PathBuf,
+  time: NaiveDateTime,
+  roller: Roller,
+}
+
+/// An output writing to a `RotatingFile` which other outputs may share. The
+/// bytes of a record are collected and written at once on `flush`, which
+/// fern calls after every record, so the records of different outputs never
+/// interleave.
+pub(crate) struct SharedFile {
+  file: Arc<Mutex<RotatingFile>>,
+  buffer: Vec<u8>,
+}
+
+impl RotationClock {
+  fn now(self) -> NaiveDateTime {
+    match self {
+      RotationClock::Local => Local::now().naive_local(),
+      RotationClock::Utc => Utc::now().naive_utc(),
+    }
+  }
+
+  fn at(self, time: SystemTime) -> NaiveDateTime {
+    match self {
+      RotationClock::Local => DateTime::<Local>::from(time).naive_local(),
+      RotationClock::Utc => DateTime::<Utc>::from(time).naive_utc(),
+    }
+  }
+}
+
+impl RotationPeriod {
+  /// Returns the start of the period which contains `time`.
+  fn start(self, time: NaiveDateTime) -> NaiveDateTime {
+    let midnight = time.date().and_time(NaiveTime::MIN);
+    match self {
+      RotationPeriod::Hourly => midnight + TimeDelta::hours(time.hour() as i64),
+      RotationPeriod::Daily => midnight,
+      RotationPeriod::Weekly => {
+        midnight - TimeDelta::days(time.weekday().num_days_from_monday() as i64)
+      }
+      RotationPeriod::Interval(_) => {
+        let timestamp = time.and_utc().timestamp();
+        let start = timestamp - timestamp.rem_euclid(self.interval_secs());
+        DateTime::from_timestamp(start, 0).map_or(time, |start| start.naive_utc())
+      }
+    }
+  }
+
+  fn end(self, start: NaiveDateTime) -> NaiveDateTime {
+    start
+      + match self {
+        RotationPeriod::Hourly => TimeDelta::hours(1),
+        RotationPeriod::Daily => TimeDelta::days(1),
+        RotationPeriod::Weekly => TimeDelta::weeks(1),
+        RotationPeriod::Interval(_) => TimeDelta::seconds(self.interval_secs()),
+      }
+  }
+
+  fn interval_secs(self) -> i64 {
+    match self {
+      RotationPeriod::Interval(interval) => interval.as_secs().clamp(1, i64::MAX as u64) as i64,
+      _ => 1,
+    }
+  }
+
+  fn label_format(self) -> &'static str {
+    match self {
+      RotationPeriod::Hourly => "%Y-%m-%dT%H",
+      RotationPeriod::Daily | RotationPeriod::Weekly => "%Y-%m-%d",
+      RotationPeriod::Interval(_) => "%Y-%m-%dT%H-%M-%S",
+    }
+  }
+}
+
+impl RotatingFile {
+  /// Opens the file, or takes over the one already open at the same path,
+  /// applying `naming` and `policy` to it.
+  pub(crate) fn open_shared(
+    dir: &Path, naming: &FileNaming, policy: RotationPolicy,
+  ) -> error::Result<SharedFile> {
+    let path = fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf()).join(naming.file_name());
+    let mut files = lock(&OPEN_FILES);
+    files.retain(|(_, file)| file.strong_count() > 0);
+    let open = files.iter().find(|(open, _)| *open == path).and_then(|(_, file)| file.upgrade());
+    let file = match open {
+      Some(file) => {
+        lock(&file).reconfigure(naming, policy)?;
+        file
+      }
+      None => {
+        let file = Arc::new(Mutex::new(Self::open(dir, naming, policy)?));
+        files.push((path, Arc::downgrade(&file)));
+        file
+      }
+    };
+    Ok(SharedFile { file, buffer: Vec::new() })
+  }
+
+  pub(crate) fn open(
+    dir: &Path, naming: &FileNaming, policy: RotationPolicy,
+  ) -> error::Result<Self> {
+    let template = naming.template(policy.period.is_some()).map_err(Error::InvalidConfig)?;
+    let roller = Roller { path: dir.join(naming.file_name()), template, policy };
+    let rotate_error = |source| Error::Rotate { path: roller.path.clone(), source };
+    roller.remove_outdated().map_err(rotate_error)?;
+
+    let now = roller.policy.clock.now();
+    let period_start = roller.policy.period.map(|period| period.start(now));
+    let (worker, worker_thread) = match roller.policy.compression {
+      Some(_) => {
+        let (worker, worker_thread) = Self::spawn_worker(&roller)?;
+        (Some(worker), Some(worker_thread))
+      }
+      None => (None, None),
+    };
+    Self::rotate_stale(&roller, worker.as_ref(), now, period_start).map_err(rotate_error)?;
+
+    let (size, file) = Self::open_file(&roller.path)
+      .and_then(|file| Ok((file.metadata()?.len() as u128, BufWriter::new(file))))
+      .map_err(|source| Error::OpenFile { path: roller.path.clone(), source })?;
+    Ok(Self { roller, file, size, period_start, in_record: false, worker, worker_thread })
+  }
+
+  /// Applies the naming and policy of a reopened target to the open file. The
+  /// period in progress goes on unless the period itself changed.
+  fn reconfigure(&mut self, naming: &FileNaming, policy: RotationPolicy) -> error::Result<()> {
+    let template = naming.template(policy.period.is_some()).map_err(Error::InvalidConfig)?;
+    let same_period =
+      policy.period == self.roller.policy.period && policy.clock == self.roller.policy.clock;
+    self.roller = Roller { path: self.roller.path.clone(), template, policy };
+    match (self.roller.policy.compression, self.worker.is_some()) {
+      (Some(_), false) => {
+        let (worker, worker_thread) = Self::spawn_worker(&self.roller)?;
+        self.worker = Some(worker);
+        self.worker_thread = Some(worker_thread);
+      }
+      (None, true) => self.stop_worker(),
+      _ => {}
+    }
+    if !same_period {
+      let now = self.roller.policy.clock.now();
+      self.period_start = self.roller.policy.period.map(|period| period.start(now));
+    }
+    self
+      .roller
+      .remove_outdated()
+      .map_err(|source| Error::Rotate { path: self.roller.path.clone(), source })
+  }
+
+  /// Rotates the file left by a previous run if its period has ended or it
+  /// is too big already.
+  fn rotate_stale(
+    roller: &Roller, worker: Option<&Sender<Staged>>, now: NaiveDateTime,
+    period_start: Option<NaiveDateTime>,
+  ) -> io::Result<()> {
+    if !roller.path.exists() {
+      return Ok(());
+    }
+    let metadata = fs::metadata(&roller.path)?;
+    let modified = roller.policy.clock.at(metadata.modified()?);
+    let modified_start = roller.policy.period.map(|period| period.start(modified));
+    if let (Some(modified_start), Some(period_start)) = (modified_start, period_start) {
+      if modified_start < period_start && metadata.len() > 0 {
+        Self::hand_off(roller, worker, modified_start)?;
+      }
+    }
+    if roller.path.exists() && metadata.len() as u128 > roller.policy.max_file_size {
+      Self::hand_off(roller, worker, period_start.unwrap_or(now))?;
+    }
+    Ok(())
+  }
+
+  fn rotate(&mut self) -> io::Result<()> {
+    self.file.flush()?;
+    let time = self.period_start.unwrap_or_else(|| self.roller.policy.clock.now());
+    Self::hand_off(&self.roller, self.worker.as_ref(), time)?;
+    self.file = BufWriter::new(Self::open_file(&self.roller.path)?);
+    self.size = 0;
+    Ok(())
+  }
+
+  /// Rotates the file if the period it was opened in has ended.
+  fn check_period(&mut self) -> io::Result<()> {
+    if let (Some(period), Some(start)) = (self.roller.policy.period, self.period_start) {
+      let now = self.roller.policy.clock.now();
+      if now >= period.end(start) {
+        if self.size > 0 {
+          self.rotate()?;
+        }
+        self.period_start = Some(period.start(now));
+      }
+    }
+    Ok(())
+  }
+
+  /// Moves the file out of the way of a fresh one. With a worker, the file is
+  /// only renamed to a staging name here and rolled on the worker's thread.
+  fn hand_off(
+    roller: &Roller, worker: Option<&Sender<Staged>>, time: NaiveDateTime,
+  ) -> io::Result<()> {
+    match worker {
+      Some(worker) => {
+        let staged = Staged { path: roller.staging_path(), time, roller: roller.clone() };
+        fs::rename(&roller.path, &staged.path)?;
+        if let Err(SendError(staged)) = worker.send(staged) {
+          staged.roller.roll(&staged.path, staged.time)?;
+        }
+        Ok(())
+      }
+      None => roller.roll(&roller.path, time),
+    }
+  }
+
+  /// Spawns the thread which rolls and compresses the staged files, picking
+  /// up any left behind by a previous run first.
+  fn spawn_worker(roller: &Roller) -> error::Result<(Sender<Staged>, JoinHandle<()>)> {
+    let (sender, receiver) = mpsc::channel::<Staged>();
+    for index in 0.. {
+      let staged = roller.staging_path_at(index);
+      if !staged.exists() {
+        break;
+      }
+      let modified = fs::metadata(&staged)
+        .and_then(|metadata| metadata.modified())
+        .map_err(|source| Error::Rotate { path: staged.clone(), source })?;
+      let modified = roller.policy.clock.at(modified);
+      let time = roller.policy.period.map_or(modified, |period| period.start(modified));
+      let _ = sender.send(Staged { path: staged, time, roller: roller.clone() });
+    }
+    let thread = thread::Builder::new()
+      .name("yaslog-rotation".to_string())
+      .spawn(move || {
+        for staged in receiver {
+          if let Err(err) = staged.roller.roll(&staged.path, staged.time) {
+            eprintln!("yaslog: failed to rotate {}: {}", staged.path.display(), err);
+          }
+        }
+      })
+      .map_err(Error::Spawn)?;
+    Ok((sender, thread))
+  }
+
+  /// Waits for the worker to roll the staged files, so a file reopened at the
+  /// same path doesn't pick them up a second time.
+  fn stop_worker(&mut self) {
+    self.worker = None;
+    if let Some(worker_thread) = self.worker_thread.take() {
+      let _ = worker_thread.join();
+    }
+  }
+
+  fn open_file(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+  }
+}
+
+impl Roller {
+  fn dir(&self) -> &Path {
+    match self.path.parent() {
+      Some(dir) if !dir.as_os_str().is_empty() => dir,
+      _ => Path::new("."),
+    }
+  }
+
+  fn timestamp_format(&self) -> &'static str {
+    self.policy.period.map_or(DEFAULT_TIMESTAMP_FORMAT, RotationPeriod::label_format)
+  }
+
+  /// Returns the first free one of `app.log.rotating`, `app.log.rotating.1`, ...
+  fn staging_path(&self) -> PathBuf {
+    (0..).map(|index| self.staging_path_at(index)).find(|staged| !staged.exists()).unwrap()
+  }
+
+  fn staging_path_at(&self, index: usize) -> PathBuf {
+    let mut staged = OsString::from(&self.path);
+    staged.push(".rotating");
+    if index > 0 {
+      staged.push(format!(".{}", index));
+    }
+    PathBuf::from(staged)
+  }
+
+  /// Moves `source` to its rotated name, with `time` as its timestamp, then
+  /// compresses it and removes the outdated rotated files.
+  fn roll(&self, source: &Path, time: NaiveDateTime) -> io::Result<()> {
+    let rotated = if self.template.is_timestamped() {
+      let rotated = self.timestamped_path(&time.format(self.timestamp_format()).to_string())?;
+      fs::rename(source, &rotated)?;
+      rotated
+    } else {
+      self.shift(source)?
+    };
+    if let Some(compression) = self.policy.compression {
+      if rotated.exists() {
+        compression.compress(&rotated)?;
+      }
+    }
+    self.remove_outdated()
+  }
+
+  /// Moves the file of generation `i` to generation `i + 1` and `source` to
+  /// generation 1, deleting whatever falls off the end. Compressed files move
+  /// along with the plain ones.
+  fn shift(&self, source: &Path) -> io::Result<PathBuf> {
+    let max_rotated_files = self.policy.max_rotated_files;
+    if max_rotated_files == 0 {
+      fs::remove_file(source)?;
+      return Ok(source.to_path_buf());
+    }
+    for oldest in Self::with_compressed(self.indexed_path(max_rotated_files)) {
+      if oldest.exists() {
+        fs::remove_file(&oldest)?;
+      }
+    }
+    for index in (1..max_rotated_files).rev() {
+      let from = Self::with_compressed(self.indexed_path(index));
+      let to = Self::with_compressed(self.indexed_path(index + 1));
+      for (from, to) in from.zip(to) {
+        if from.exists() {
+          fs::rename(&from, to)?;
+        }
+      }
+    }
+    let rotated = self.indexed_path(1);
+    fs::rename(source, &rotated)?;
+    Ok(rotated)
+  }
+
+  fn indexed_path(&self, index: usize) -> PathBuf {
+    self.dir().join(self.template.render(index, ""))
+  }
+
+  /// Returns the name for `timestamp` after those already taken, numbering
+  /// the files which share it with `{index}` from 1, or with a `.N` suffix of
+  /// the timestamp if the template has no `{index}`.
+  fn timestamped_path(&self, timestamp: &str) -> io::Result<PathBuf> {
+    let last = self
+      .rotated_files()?
+      .into_iter()
+      .filter(|rotated| rotated.generation.timestamp == timestamp)
+      .map(|rotated| rotated.generation.index)
+      .max();
+    let name = match last {
+      _ if self.template.is_indexed() => {
+        self.template.render(last.map_or(1, |last| last + 1), timestamp)
+      }
+      None => self.template.render(0, timestamp),
+      Some(last) => self.template.render(0, &format!("{}.{}", timestamp, last + 1)),
+    };
+    Ok(self.dir().join(name))
+  }
+
+  /// Returns `path` followed by its compressed variants, `path.gz` and so on.
+  fn with_compressed(path: PathBuf) -> impl Iterator<Item = PathBuf> {
+    let compressed = COMPRESSED_EXTENSIONS.iter().map({
+      let path = path.clone();
+      move |extension| {
+        let mut compressed = OsString::from(&path);
+        compressed.push(format!(".{}", extension));
+        PathBuf::from(compressed)
+      }
+    });
+    iter::once(path).chain(compressed)
+  }
+
+  /// Deletes the oldest rotated files beyond `max_rotated_files` or
+  /// `max_total_size`, and those older than `max_age`.
+  fn remove_outdated(&self) -> io::Result<()> {
+    let mut rotated = self.rotated_files()?;
+    self.sort_newest_first(&mut rotated);
+    let now = SystemTime::now();
+    let mut total_size = 0;
+    for (count, RotatedFile { path: rotated, modified, size, .. }) in
+      rotated.into_iter().enumerate()
+    {
+      total_size += size;
+      let age = now.duration_since(modified).unwrap_or_default();
+      if count >= self.policy.max_rotated_files
+        || self.policy.max_total_size.is_some_and(|max_total_size| total_size > max_total_size)
+        || self.policy.max_age.is_some_and(|max_age| age > max_age)
+      {
+        match fs::remove_file(&rotated) {
+          Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+          _ => {}
+        }
+      }
+    }
+    Ok(())
+  }
+
+  /// Sorts the rotated files from the newest to the oldest after their names,
+  /// as files rotated in a burst share their modification times. Indexed
+  /// generations get older as their index grows. Timestamped ones are ordered
+  /// by the latest modification time of their timestamp, so a change of the
+  /// clock doesn't reorder them, then get newer as their index grows.
+  fn sort_newest_first(&self, rotated: &mut [RotatedFile]) {
+    if !self.template.is_timestamped() {
+      rotated.sort_by_key(|file| file.generation.index);
+      return;
+    }
+    let mut latest = HashMap::<String, SystemTime>::new();
+    for file in rotated.iter() {
+      let modified = latest.entry(file.generation.timestamp.clone()).or_insert(file.modified);
+      *modified = file.modified.max(*modified);
+    }
+    rotated.sort_by(|a, b| {
+      let (a, b) = (&a.generation, &b.generation);
+      latest[&b.timestamp]
+        .cmp(&latest[&a.timestamp])
+        .then_with(|| b.timestamp.cmp(&a.timestamp))
+        .then_with(|| b.index.cmp(&a.index))
+    });
+  }
+
+  /// Lists the rotated files, possibly compressed, with their modification
+  /// times, sizes and generations. Only the names the template could have
+  /// produced are listed, so nothing else in the directory is touched.
+  fn rotated_files(&self) -> io::Result<Vec<RotatedFile>> {
+    let dir = self.dir();
+    if !dir.exists() {
+      return Ok(Vec::new());
+    }
+    let mut rotated = Vec::new();
+    for entry in fs::read_dir(dir)? {
+      let entry = entry?;
+      let name = entry.file_name();
+      let name = match name.to_str() {
+        Some(name) => name,
+        None => continue,
+      };
+      let name = COMPRESSED_EXTENSIONS
+        .iter()
+        .find_map(|compressed| name.strip_suffix(compressed)?.strip_suffix('.'))
+        .unwrap_or(name);
+      if let Some(generation) = self.template.parse(name, self.timestamp_format()) {
+        let metadata = entry.metadata()?;
+        rotated.push(RotatedFile {
+          path: entry.path(),
+          modified: metadata.modified()?,
+          size: metadata.len() as u128,
+          generation,
+        });
+      }
+    }
+    Ok(rotated)
+  }
+}
+
+impl Drop for RotatingFile {
+  fn drop(&mut self) {
+    self.stop_worker();
+  }
+}
+
+impl Write for RotatingFile {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    if !self.in_record {
+      self.in_record = true;
+      self.check_period()?;
+    }
+    let written = self.file.write(buf)?;
+    self.size += written as u128;
+    Ok(written)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.in_record = false;
+    self.file.flush()?;
+    if self.size > self.roller.policy.max_file_size {
+      self.rotate()?;
+    }
+    Ok(())
+  }
+}
+
+impl Write for SharedFile {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.buffer.extend_from_slice(buf);
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    let mut file = lock(&self.file);
+    if !self.buffer.is_empty() {
+      let record = mem::take(&mut self.buffer);
+      file.write_all(&record)?;
+    }
+    file.flush()
+  }
+}
+
+/// Locks `mutex`, ignoring poisoning, as logging must go on after a panic.
+fn lock<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+  mutex.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::NaiveDate;
+
+  use super::*;
+
+  /// A fresh directory under the system's temporary one.
+  fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("yaslog-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+  }
+
+  fn policy(period: Option<RotationPeriod>, max_rotated_files: usize) -> RotationPolicy {
+    RotationPolicy {
+      max_file_size: 50,
+      max_rotated_files,
+      period,
+      clock: RotationClock::Local,
+      compression: None,
+      max_total_size: None,
+      max_age: None,
+    }
+  }
+
+  fn roller(dir: &Path, policy: RotationPolicy) -> Roller {
+    let naming = FileNaming::default();
+    let template = naming.template(policy.period.is_some()).unwrap();
+    Roller { path: dir.join(naming.file_name()), template, policy }
+  }
+
+  /// Creates `names` modified `ago`, all at once as in a burst.
+  fn create(dir: &Path, names: &[&str], ago: Duration) {
+    let modified = SystemTime::now() - ago;
+    for name in names {
+      let file = File::create(dir.join(name)).unwrap();
+      file.set_modified(modified).unwrap();
+    }
+  }
+
+  fn names(dir: &Path) -> Vec<String> {
+    let mut names = fs::read_dir(dir)
+      .unwrap()
+      .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+      .collect::<Vec<_>>();
+    names.sort();
+    names
+  }
+
+  #[test]
+  fn keeps_max_rotated_files() {
+    let dir = temp_dir("count");
+    for (index, name) in ["app.log.1", "app.log.2", "app.log.3"].into_iter().enumerate() {
+      create(&dir, &[name], Duration::from_secs(60 * index as u64));
+    }
+    roller(&dir, policy(None, 2)).remove_outdated().unwrap();
+    assert_eq!(names(&dir), ["app.log.1", "app.log.2"]);
+    fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[test]
+  fn keeps_max_total_size() {
+    let dir = temp_dir("size");
+    for (index, name) in ["app.log.1", "app.log.2", "app.log.3"].into_iter().enumerate() {
+      fs::write(dir.join(name), "0123456789").unwrap();
+      let ago = Duration::from_secs(60 * index as u64);
+      File::options()
+        .write(true)
+        .open(dir.join(name))
+        .unwrap()
+        .set_modified(SystemTime::now() - ago)
+        .unwrap();
+    }
+    let policy = RotationPolicy { max_total_size: Some(25), ..policy(None, 5) };
+    roller(&dir, policy).remove_outdated().unwrap();
+    assert_eq!(names(&dir), ["app.log.1", "app.log.2"]);
+    fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[test]
+  fn removes_files_older_than_max_age() {
+    let dir = temp_dir("age");
+    create(&dir, &["app.2026-10-18.log"], Duration::from_secs(2 * 60 * 60));
+    create(&dir, &["app.2026-10-19.log.gz"], Duration::ZERO);
+    let policy = RotationPolicy {
+      max_age: Some(Duration::from_secs(60 * 60)),
+      ..policy(Some(RotationPeriod::Daily), 5)
+    };
+    roller(&dir, policy).remove_outdated().unwrap();
+    assert_eq!(names(&dir), ["app.2026-10-19.log.gz"]);
+    fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[test]
+  fn leaves_other_files_alone() {
+    let dir = temp_dir("others");
+    let others = ["app.2026-10.log", "app.log", "app.log.2.zst", "app.log.rotating", "notes.txt"];
+    create(&dir, &others, Duration::ZERO);
+    create(&dir, &["app.2026-10-19.1.log", "app.2026-10-18.log.gz"], Duration::ZERO);
+    roller(&dir, policy(Some(RotationPeriod::Daily), 0)).remove_outdated().unwrap();
+    assert_eq!(names(&dir), others);
+    fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[test]
+  fn keeps_the_newest_files_of_a_burst() {
+    let dir = temp_dir("burst");
+    let names_of_the_day = ["app.2026-10-19.log", "app.2026-10-19.1.log", "app.2026-10-19.2.log"];
+    create(&dir, &names_of_the_day, Duration::ZERO);
+    create(&dir, &["app.2026-10-19.10.log", "app.log", "notes.txt"], Duration::ZERO);
+    roller(&dir, policy(Some(RotationPeriod::Daily), 2)).remove_outdated().unwrap();
+    assert_eq!(
+      names(&dir),
+      ["app.2026-10-19.10.log", "app.2026-10-19.2.log", "app.log", "notes.txt"]
+    );
+    fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[test]
+  fn keeps_the_newest_timestamps() {
+    let dir = temp_dir("timestamps");
+    create(&dir, &["app.2026-10-17.log", "app.2026-10-17.1.log"], Duration::from_secs(60));
+    create(&dir, &["app.2026-10-18.log"], Duration::ZERO);
+    roller(&dir, policy(Some(RotationPeriod::Daily), 2)).remove_outdated().unwrap();
+    assert_eq!(names(&dir), ["app.2026-10-17.1.log", "app.2026-10-18.log"]);
+    fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[test]
+  fn keeps_the_lowest_indexes_of_a_burst() {
+    let dir = temp_dir("indexed-burst");
+    create(&dir, &["app.log.1", "app.log.2", "app.log.3", "app.log.10"], Duration::ZERO);
+    roller(&dir, policy(None, 2)).remove_outdated().unwrap();
+    assert_eq!(names(&dir), ["app.log.1", "app.log.2"]);
+    fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[test]
+  fn numbers_timestamped_files_after_the_last_one() {
+    let dir = temp_dir("numbering");
+    let roller = roller(&dir, policy(Some(RotationPeriod::Daily), 5));
+    let next = || roller.timestamped_path("2026-10-19").unwrap();
+    assert_eq!(next(), dir.join("app.2026-10-19.log"));
+    create(&dir, &["app.2026-10-19.log"], Duration::ZERO);
+    assert_eq!(next(), dir.join("app.2026-10-19.1.log"));
+    create(&dir, &["app.2026-10-19.3.log.gz"], Duration::ZERO);
+    assert_eq!(next(), dir.join("app.2026-10-19.4.log"));
+    fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[test]
+  fn shares_the_file_open_at_the_same_path() {
+    let dir = temp_dir("sharing");
+    let naming = FileNaming::default();
+    let mut first = RotatingFile::open_shared(&dir, &naming, policy(None, 5)).unwrap();
+    let mut second = RotatingFile::open_shared(&dir, &naming, policy(None, 2)).unwrap();
+    assert!(Arc::ptr_eq(&first.file, &second.file));
+    assert_eq!(lock(&first.file).roller.policy.max_rotated_files, 2);
+    first.write_all(b"first ").unwrap();
+    second.write_all(b"second\n").unwrap();
+    first.write_all(b"record\n").unwrap();
+    second.flush().unwrap();
+    first.flush().unwrap();
+    assert_eq!(fs::read_to_string(dir.join("app.log")).unwrap(), "second\nfirst record\n");
+    drop((first, second));
+    fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[test]
+  fn rolls_over_once_flushed_past_max_file_size() {
+    let dir = temp_dir("size");
+    let mut file = RotatingFile::open(&dir, &FileNaming::default(), policy(None, 5)).unwrap();
+    let record = [b'a'; 29];
+    file.write_all(&record).unwrap();
+    file.flush().unwrap();
+    assert_eq!(names(&dir), ["app.log"]);
+    file.write_all(&record).unwrap();
+    file.flush().unwrap();
+    assert_eq!(names(&dir), ["app.log", "app.log.1"]);
+    assert_eq!(fs::read(dir.join("app.log.1")).unwrap(), [b'a'; 58]);
+    file.write_all(b"next").unwrap();
+    file.flush().unwrap();
+    assert_eq!(fs::read_to_string(dir.join("app.log")).unwrap(), "next");
+    drop(file);
+    fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[test]
+  fn finds_the_period_bounds() {
+    let at = |day, hour, minute| {
+      NaiveDate::from_ymd_opt(2026, 10, day).unwrap().and_hms_opt(hour, minute, 0).unwrap()
+    };
+    // 2026-10-19 is a Monday.
+    let weekly = RotationPeriod::Weekly;
+    assert_eq!(weekly.start(at(21, 15, 30)), at(19, 0, 0));
+    assert_eq!(weekly.start(at(19, 0, 0)), at(19, 0, 0));
+    assert_eq!(weekly.start(at(18, 23, 59)), at(12, 0, 0));
+    assert_eq!(weekly.end(at(19, 0, 0)), at(26, 0, 0));
+    let interval = RotationPeriod::Interval(Duration::from_secs(90 * 60));
+    assert_eq!(interval.start(at(19, 1, 31)), at(19, 1, 30));
+    assert_eq!(interval.start(at(19, 1, 29)), at(19, 0, 0));
+    assert_eq!(interval.end(at(19, 1, 30)), at(19, 3, 0));
+    // Counted from 1970-01-01, so 7 hours don't start at midnight.
+    let interval = RotationPeriod::Interval(Duration::from_secs(7 * 60 * 60));
+    let start = interval.start(at(19, 12, 0));
+    assert_eq!(start.and_utc().timestamp() % (7 * 60 * 60), 0);
+    assert!(start <= at(19, 12, 0) && at(19, 12, 0) < interval.end(start));
+  }
+
+  #[test]
+  fn rolls_over_when_the_period_ends() {
+    let dir = temp_dir("period");
+    let policy = policy(Some(RotationPeriod::Daily), 5);
+    let mut file = RotatingFile::open(&dir, &FileNaming::default(), policy).unwrap();
+    file.write_all(b"old\n").unwrap();
+    file.flush().unwrap();
+    let yesterday = file.period_start.unwrap() - TimeDelta::days(1);
+    file.period_start = Some(yesterday);
+    file.write_all(b"new\n").unwrap();
+    file.flush().unwrap();
+    let rotated = format!("app.{}.log", yesterday.format("%Y-%m-%d"));
+    assert_eq!(names(&dir), [rotated.clone(), "app.log".to_string()]);
+    assert_eq!(fs::read_to_string(dir.join(rotated)).unwrap(), "old\n");
+    assert_eq!(fs::read_to_string(dir.join("app.log")).unwrap(), "new\n");
+    assert_eq!(file.period_start, Some(RotationPeriod::Daily.start(RotationClock::Local.now())));
+    drop(file);
+    fs::remove_dir_all(dir).unwrap();
+  }
+}
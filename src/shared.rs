@@ -0,0 +1,170 @@
+use std::{
+  cell::Cell,
+  sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard,
+  },
+};
+
+use log::{Log, Metadata, Record};
+
+use crate::{
+  error::{Error, Result},
+  filter::Levels,
+  logger::LoggerBuilder,
+};
+
+/// A logger whose dispatch and levels can be replaced while other threads log
+/// through it.
+pub(crate) struct SharedLog {
+  current: RwLock<Current>,
+  /// Held while reloading, so the files shared by concurrent reloads end up
+  /// configured as the dispatch installed last.
+  reloading: Mutex<()>,
+  /// Set once the logger is shut down, after which it is never reloaded.
+  shut_down: AtomicBool,
+  /// Records dropped by the asynchronous outputs.
+  pub(crate) dropped: Arc<AtomicU64>,
+  /// Whether this is the global logger, whose levels set the global maximum.
+  global: bool,
+}
+
+/// The dispatch in use, replaced along with its levels.
+pub(crate) struct Current {
+  pub(crate) log: Box<dyn Log>,
+  /// Also read by the targets following the default levels.
+  pub(crate) levels: Arc<RwLock<Levels>>,
+}
+
+impl SharedLog {
+  pub(crate) fn new(current: Current, dropped: Arc<AtomicU64>, global: bool) -> Self {
+    Self {
+      current: RwLock::new(current),
+      reloading: Mutex::new(()),
+      shut_down: AtomicBool::new(false),
+      dropped,
+      global,
+    }
+  }
+
+  /// Replaces the dispatch by one discarding the records for good and
+  /// returns it.
+  pub(crate) fn take(&self) -> Box<dyn Log> {
+    let _reloading = lock(&self.reloading);
+    self.shut_down.store(true, Ordering::Relaxed);
+    std::mem::replace(&mut write(&self.current).log, Box::new(Discard))
+  }
+
+  pub(crate) fn is_shut_down(&self) -> bool {
+    self.shut_down.load(Ordering::Relaxed)
+  }
+
+  /// Opens the outputs of `builder`, then replaces the dispatch and its
+  /// levels by them. The previous dispatch goes on until the swap and is
+  /// kept if `builder` fails; the files it shares with the new one are
+  /// written by a single `RotatingFile` throughout. Fails once shut down.
+  pub(crate) fn reload(&self, builder: LoggerBuilder) -> Result<()> {
+    let _reloading = lock(&self.reloading);
+    if self.is_shut_down() {
+      return Err(Error::ShutDown);
+    }
+    let opened = builder.open(&self.dropped)?;
+    let previous = std::mem::replace(&mut *write(&self.current), opened);
+    if self.global {
+      log::set_max_level(self.read_levels(Levels::max_level));
+    }
+    previous.log.flush();
+    Ok(())
+  }
+
+  /// Returns what `f` reads from the levels.
+  pub(crate) fn read_levels<T>(&self, f: impl FnOnce(&Levels) -> T) -> T {
+    f(&read(&read(&self.current).levels))
+  }
+
+  /// Changes the levels, and the global maximum level to match them if this
+  /// is the global logger.
+  pub(crate) fn update_levels(&self, f: impl FnOnce(&mut Levels)) {
+    let current = read(&self.current);
+    let mut levels = write(&current.levels);
+    f(&mut levels);
+    if self.global {
+      log::set_max_level(levels.max_level());
+    }
+  }
+}
+
+impl Log for SharedLog {
+  fn enabled(&self, metadata: &Metadata) -> bool {
+    let current = read(&self.current);
+    let enabled = read(&current.levels).enabled(metadata);
+    enabled && current.log.enabled(metadata)
+  }
+
+  fn log(&self, record: &Record) {
+    let current = read(&self.current);
+    if read(&current.levels).enabled(record.metadata()) {
+      let _logging = Logging::enter();
+      current.log.log(record)
+    }
+  }
+
+  fn flush(&self) {
+    let _logging = Logging::enter();
+    read(&self.current).log.flush()
+  }
+}
+
+thread_local! {
+  static LOGGING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Whether this thread is inside a logger, e.g. formatting a record while it
+/// holds the lock of an output.
+pub(crate) fn is_logging() -> bool {
+  LOGGING.with(Cell::get)
+}
+
+/// Marks this thread as inside a logger until dropped, even by unwinding.
+struct Logging {
+  was_logging: bool,
+}
+
+impl Logging {
+  fn enter() -> Self {
+    Self { was_logging: LOGGING.with(|logging| logging.replace(true)) }
+  }
+}
+
+impl Drop for Logging {
+  fn drop(&mut self) {
+    LOGGING.with(|logging| logging.set(self.was_logging));
+  }
+}
+
+/// The dispatch of a logger which was shut down.
+struct Discard;
+
+impl Log for Discard {
+  fn enabled(&self, _: &Metadata) -> bool {
+    false
+  }
+
+  fn log(&self, _: &Record) {}
+
+  fn flush(&self) {}
+}
+
+/// Locks `lock` for reading, ignoring poisoning, as logging must go on after
+/// a panic.
+pub(crate) fn read<T: ?Sized>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+  lock.read().unwrap_or_else(|err| err.into_inner())
+}
+
+pub(crate) fn write<T: ?Sized>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+  lock.write().unwrap_or_else(|err| err.into_inner())
+}
+
+fn lock<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+  mutex.lock().unwrap_or_else(|err| err.into_inner())
+}
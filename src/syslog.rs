@@ -0,0 +1,457 @@
+#[cfg(unix)]
+use std::os::unix::net::UnixDatagram;
+use std::{
+  env, fmt,
+  io::{self, Write},
+  net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, ToSocketAddrs, UdpSocket},
+  path::{Path, PathBuf},
+  process,
+  str::FromStr,
+};
+
+use chrono::{Local, SecondsFormat};
+use log::{Level, Record};
+
+use crate::error::{Error, Result};
+
+/// The longest app name and host name of RFC 5424.
+const MAX_APP_NAME_LEN: usize = 48;
+const MAX_HOSTNAME_LEN: usize = 255;
+
+/// Syslog facilities, telling the daemon which kind of program logs, with
+/// their RFC 5424 codes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Facility {
+  Kern,
+  #[default]
+  User,
+  Mail,
+  Daemon,
+  Auth,
+  Syslog,
+  Lpr,
+  News,
+  Uucp,
+  Cron,
+  Authpriv,
+  Ftp,
+  Local0 = 16,
+  Local1,
+  Local2,
+  Local3,
+  Local4,
+  Local5,
+  Local6,
+  Local7,
+}
+
+/// Formats of syslog messages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyslogProtocol {
+  /// The BSD format, `<PRI>Mmm dd hh:mm:ss HOSTNAME APP[PID]: MSG`.
+  Rfc3164,
+  /// The IETF format, `<PRI>1 TIMESTAMP HOSTNAME APP PID - - MSG`.
+  Rfc5424,
+}
+
+#[derive(Clone, Debug)]
+enum Transport {
+  #[cfg(unix)]
+  Unix(PathBuf),
+  Udp(String),
+  Tcp(String),
+}
+
+/// A syslog daemon to log to, and how.
+///
+/// The records are laid out in the logger's or the target's format after the
+/// syslog header, and their level is mapped to the syslog severity: `Error`
+/// to err, `Warn` to warning, `Info` to info and `Debug` and `Trace` to debug.
+#[derive(Clone, Debug)]
+pub struct Syslog {
+  transport: Transport,
+  protocol: SyslogProtocol,
+  facility: Facility,
+  app_name: Option<String>,
+  hostname: Option<String>,
+}
+
+impl Syslog {
+  /// Logs to the local daemon through `/dev/log` in the RFC 3164 format.
+  #[cfg(unix)]
+  pub fn local() -> Self {
+    Self::unix("/dev/log")
+  }
+
+  /// Logs to the daemon listening on the Unix datagram socket at `path` in
+  /// the RFC 3164 format.
+  #[cfg(unix)]
+  pub fn unix<P: Into<PathBuf>>(path: P) -> Self {
+    Self::new(Transport::Unix(path.into()), SyslogProtocol::Rfc3164)
+  }
+
+  /// Logs to the daemon at `address`, e.g. `"logs.example.com:514"`, over UDP
+  /// in the RFC 5424 format.
+  pub fn udp<T: Into<String>>(address: T) -> Self {
+    Self::new(Transport::Udp(address.into()), SyslogProtocol::Rfc5424)
+  }
+
+  /// Logs to the daemon at `address` over TCP in the RFC 5424 format, the
+  /// messages being framed by their length as in RFC 6587, so records may
+  /// span several lines.
+  pub fn tcp<T: Into<String>>(address: T) -> Self {
+    Self::new(Transport::Tcp(address.into()), SyslogProtocol::Rfc5424)
+  }
+
+  fn new(transport: Transport, protocol: SyslogProtocol) -> Self {
+    Self { transport, protocol, facility: Facility::User, app_name: None, hostname: None }
+  }
+
+  pub fn protocol(mut self, protocol: SyslogProtocol) -> Self {
+    self.protocol = protocol;
+    self
+  }
+
+  /// Sets the facility, `User` by default.
+  pub fn facility(mut self, facility: Facility) -> Self {
+    self.facility = facility;
+    self
+  }
+
+  /// Sets the name of the program, the one of its executable by default.
+  /// Like the host name, it is cut to the length RFC 5424 allows and any
+  /// character but printable ASCII is replaced by `_` in the header.
+  pub fn app_name<T: Into<String>>(mut self, app_name: T) -> Self {
+    self.app_name = Some(app_name.into());
+    self
+  }
+
+  /// Sets the host name, the one of the machine by default.
+  pub fn hostname<T: Into<String>>(mut self, hostname: T) -> Self {
+    self.hostname = Some(hostname.into());
+    self
+  }
+
+  /// Returns the header writer and the output of this daemon.
+  pub(crate) fn open(&self) -> Result<(Header, SyslogWriter)> {
+    let app_name = self.app_name.clone().unwrap_or_else(|| {
+      env::args_os()
+        .next()
+        .as_deref()
+        .and_then(|program| Path::new(program).file_name())
+        .map_or_else(|| "-".to_string(), |name| name.to_string_lossy().into_owned())
+    });
+    let hostname = self.hostname.clone().unwrap_or_else(|| {
+      hostname::get().map_or_else(|_| "-".to_string(), |name| name.to_string_lossy().into_owned())
+    });
+    let header = Header {
+      protocol: self.protocol,
+      facility: self.facility,
+      app_name: header_field(&app_name, MAX_APP_NAME_LEN),
+      hostname: header_field(&hostname, MAX_HOSTNAME_LEN),
+      pid: process::id(),
+    };
+    let socket = Socket::connect(&self.transport)
+      .map_err(|source| Error::Connect { address: self.transport.to_string(), source })?;
+    Ok((header, SyslogWriter { transport: self.transport.clone(), socket, buffer: Vec::new() }))
+  }
+}
+
+impl fmt::Display for Transport {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      #[cfg(unix)]
+      Transport::Unix(path) => write!(f, "unix:{}", path.display()),
+      Transport::Udp(address) => write!(f, "udp:{}", address),
+      Transport::Tcp(address) => write!(f, "tcp:{}", address),
+    }
+  }
+}
+
+impl FromStr for Facility {
+  type Err = String;
+
+  /// Parses the lowercase name of a facility, e.g. `daemon` or `local0`.
+  fn from_str(facility: &str) -> std::result::Result<Self, String> {
+    Ok(match facility.to_ascii_lowercase().as_str() {
+      "kern" => Facility::Kern,
+      "user" => Facility::User,
+      "mail" => Facility::Mail,
+      "daemon" => Facility::Daemon,
+      "auth" => Facility::Auth,
+      "syslog" => Facility::Syslog,
+      "lpr" => Facility::Lpr,
+      "news" => Facility::News,
+      "uucp" => Facility::Uucp,
+      "cron" => Facility::Cron,
+      "authpriv" => Facility::Authpriv,
+      "ftp" => Facility::Ftp,
+      "local0" => Facility::Local0,
+      "local1" => Facility::Local1,
+      "local2" => Facility::Local2,
+      "local3" => Facility::Local3,
+      "local4" => Facility::Local4,
+      "local5" => Facility::Local5,
+      "local6" => Facility::Local6,
+      "local7" => Facility::Local7,
+      _ => return Err(format!("unknown syslog facility `{}`", facility)),
+    })
+  }
+}
+
+impl FromStr for SyslogProtocol {
+  type Err = String;
+
+  /// Parses `rfc3164` or `rfc5424`, ignoring case.
+  fn from_str(protocol: &str) -> std::result::Result<Self, String> {
+    match protocol.to_ascii_lowercase().as_str() {
+      "rfc3164" => Ok(SyslogProtocol::Rfc3164),
+      "rfc5424" => Ok(SyslogProtocol::Rfc5424),
+      _ => Err(format!("unknown syslog protocol `{}`", protocol)),
+    }
+  }
+}
+
+/// Writes the syslog header of the records.
+pub(crate) struct Header {
+  protocol: SyslogProtocol,
+  facility: Facility,
+  app_name: String,
+  hostname: String,
+  pid: u32,
+}
+
+impl Header {
+  pub(crate) fn write(&self, f: &mut fmt::Formatter, record: &Record) -> fmt::Result {
+    let severity = match record.level() {
+      Level::Error => 3,
+      Level::Warn => 4,
+      Level::Info => 6,
+      Level::Debug | Level::Trace => 7,
+    };
+    let priority = self.facility as u8 * 8 + severity;
+    let now = Local::now();
+    match self.protocol {
+      SyslogProtocol::Rfc3164 => write!(
+        f,
+        "<{}>{} {} {}[{}]: ",
+        priority,
+        now.format("%b %e %H:%M:%S"),
+        self.hostname,
+        self.app_name,
+        self.pid
+      ),
+      SyslogProtocol::Rfc5424 => write!(
+        f,
+        "<{}>1 {} {} {} {} - - ",
+        priority,
+        now.to_rfc3339_opts(SecondsFormat::Micros, false),
+        self.hostname,
+        self.app_name,
+        self.pid
+      ),
+    }
+  }
+}
+
+/// Returns `value` as a header field of at most `max_len` printable ASCII
+/// characters, or `-` if it is empty.
+fn header_field(value: &str, max_len: usize) -> String {
+  if value.is_empty() {
+    return "-".to_string();
+  }
+  value.chars().take(max_len).map(|c| if c.is_ascii_graphic() { c } else { '_' }).collect()
+}
+
+enum Socket {
+  #[cfg(unix)]
+  Unix(UnixDatagram),
+  Udp(UdpSocket),
+  Tcp(TcpStream),
+}
+
+impl Socket {
+  fn connect(transport: &Transport) -> io::Result<Self> {
+    Ok(match transport {
+      #[cfg(unix)]
+      Transport::Unix(path) => {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(path)?;
+        Socket::Unix(socket)
+      }
+      Transport::Udp(address) => {
+        let mut last_error = None;
+        for address in address.to_socket_addrs()? {
+          let local: SocketAddr = match address {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+          };
+          match UdpSocket::bind(local).and_then(|socket| {
+            socket.connect(address)?;
+            Ok(socket)
+          }) {
+            Ok(socket) => return Ok(Socket::Udp(socket)),
+            Err(err) => last_error = Some(err),
+          }
+        }
+        return Err(last_error.unwrap_or_else(|| {
+          io::Error::new(io::ErrorKind::InvalidInput, "no address to connect to")
+        }));
+      }
+      Transport::Tcp(address) => Socket::Tcp(TcpStream::connect(address.as_str())?),
+    })
+  }
+
+  fn send(&mut self, message: &[u8]) -> io::Result<()> {
+    match self {
+      #[cfg(unix)]
+      Socket::Unix(socket) => socket.send(message).map(|_| ()),
+      Socket::Udp(socket) => socket.send(message).map(|_| ()),
+      Socket::Tcp(stream) => {
+        write!(stream, "{} ", message.len())?;
+        stream.write_all(message)
+      }
+    }
+  }
+}
+
+/// An output which sends every record to a syslog daemon as one message,
+/// when fern flushes it. It reconnects once if sending fails, e.g. after the
+/// daemon restarted.
+pub(crate) struct SyslogWriter {
+  transport: Transport,
+  socket: Socket,
+  buffer: Vec<u8>,
+}
+
+impl Write for SyslogWriter {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.buffer.extend_from_slice(buf);
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    if self.buffer.is_empty() {
+      return Ok(());
+    }
+    let message = std::mem::take(&mut self.buffer);
+    let message = message.strip_suffix(b"\n").unwrap_or(&message);
+    if self.socket.send(message).is_err() {
+      self.socket = Socket::connect(&self.transport)?;
+      self.socket.send(message)?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    io::Read,
+    net::{TcpListener, UdpSocket},
+  };
+
+  use chrono::DateTime;
+
+  use super::*;
+
+  struct Prefix<'a>(&'a Header, &'a Record<'a>);
+
+  impl fmt::Display for Prefix<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+      self.0.write(f, self.1)
+    }
+  }
+
+  fn header(protocol: SyslogProtocol, facility: Facility, level: Level) -> String {
+    let header = Header {
+      protocol,
+      facility,
+      app_name: "app".to_string(),
+      hostname: "host".to_string(),
+      pid: 42,
+    };
+    Prefix(&header, &Record::builder().args(format_args!("hello")).level(level).build()).to_string()
+  }
+
+  fn send(syslog: Syslog, messages: &[&[u8]]) {
+    let (_, mut writer) = syslog.app_name("app").hostname("host").open().unwrap();
+    for message in messages {
+      writer.write_all(message).unwrap();
+      writer.flush().unwrap();
+    }
+  }
+
+  #[test]
+  fn computes_the_priority() {
+    let priority = |facility, level| {
+      let header = header(SyslogProtocol::Rfc5424, facility, level);
+      header[1..header.find('>').unwrap()].to_string()
+    };
+    assert_eq!(priority(Facility::Kern, Level::Error), "3");
+    assert_eq!(priority(Facility::User, Level::Warn), "12");
+    assert_eq!(priority(Facility::Daemon, Level::Info), "30");
+    assert_eq!(priority(Facility::Local0, Level::Debug), "135");
+    assert_eq!(priority(Facility::Local7, Level::Trace), "191");
+  }
+
+  #[test]
+  fn writes_the_rfc_3164_header() {
+    let header = header(SyslogProtocol::Rfc3164, Facility::Local0, Level::Warn);
+    assert!(header.starts_with("<132>"), "{}", header);
+    assert!(header.ends_with(" host app[42]: "), "{}", header);
+    // `Mmm dd hh:mm:ss`, the day padded with a space.
+    assert_eq!(header.len(), "<132>".len() + 15 + " host app[42]: ".len());
+  }
+
+  #[test]
+  fn writes_the_rfc_5424_header() {
+    let header = header(SyslogProtocol::Rfc5424, Facility::User, Level::Info);
+    let fields = header.split(' ').collect::<Vec<_>>();
+    assert_eq!(fields[0], "<14>1");
+    assert!(DateTime::parse_from_rfc3339(fields[1]).is_ok(), "{}", header);
+    assert_eq!(fields[2..], ["host", "app", "42", "-", "-", ""]);
+  }
+
+  #[test]
+  fn sanitizes_header_fields() {
+    assert_eq!(header_field("my app", MAX_APP_NAME_LEN), "my_app");
+    assert_eq!(header_field("héllo\n", MAX_APP_NAME_LEN), "h_llo_");
+    assert_eq!(header_field("", MAX_APP_NAME_LEN), "-");
+    assert_eq!(header_field(&"a".repeat(60), MAX_APP_NAME_LEN).len(), 48);
+  }
+
+  #[test]
+  fn frames_tcp_messages_by_length() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    send(Syslog::tcp(address), &[b"two\nlines\n", b"hello\n"]);
+    let mut received = String::new();
+    listener.accept().unwrap().0.read_to_string(&mut received).unwrap();
+    assert_eq!(received, "9 two\nlines5 hello");
+  }
+
+  #[test]
+  fn sends_udp_datagrams() {
+    let daemon = UdpSocket::bind("127.0.0.1:0").unwrap();
+    send(Syslog::udp(daemon.local_addr().unwrap().to_string()), &[b"hello\n"]);
+    let mut buffer = [0; 64];
+    let len = daemon.recv(&mut buffer).unwrap();
+    assert_eq!(&buffer[..len], b"hello");
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn sends_unix_datagrams() {
+    let path = env::temp_dir().join(format!("yaslog-syslog-{}.sock", process::id()));
+    let _ = std::fs::remove_file(&path);
+    let daemon = UnixDatagram::bind(&path).unwrap();
+    send(Syslog::unix(&path), &[b"hello\n", b"world\n"]);
+    let mut buffer = [0; 64];
+    for expected in [b"hello", b"world"] {
+      let len = daemon.recv(&mut buffer).unwrap();
+      assert_eq!(&buffer[..len], expected);
+    }
+    std::fs::remove_file(path).unwrap();
+  }
+}
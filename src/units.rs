@@ -0,0 +1,88 @@
+use std::time::Duration;
+
+/// Parses a size in bytes like `1048576`, `512KB` or `10 MiB`. The units `K`,
+/// `M`, `G` and `T`, with or without a trailing `B` or `iB`, are powers of
+/// 1024.
+pub(crate) fn parse_size(text: &str) -> Result<u128, String> {
+  let (number, unit) = split_number(text);
+  let number: u128 = number.parse().map_err(|_| format!("invalid size `{}`", text))?;
+  let shift = match unit.to_ascii_lowercase().as_str() {
+    "" | "b" => 0,
+    "k" | "kb" | "kib" => 10,
+    "m" | "mb" | "mib" => 20,
+    "g" | "gb" | "gib" => 30,
+    "t" | "tb" | "tib" => 40,
+    _ => return Err(format!("unknown unit in size `{}`", text)),
+  };
+  number.checked_mul(1 << shift).ok_or_else(|| format!("size `{}` is too large", text))
+}
+
+/// Parses a duration like `90s`, `30m`, `12h`, `7d` or `2w`. A bare number is
+/// in seconds.
+pub(crate) fn parse_duration(text: &str) -> Result<Duration, String> {
+  let (number, unit) = split_number(text);
+  let number: u64 = number.parse().map_err(|_| format!("invalid duration `{}`", text))?;
+  let seconds = match unit.to_ascii_lowercase().as_str() {
+    "" | "s" => 1,
+    "m" => 60,
+    "h" => 60 * 60,
+    "d" => 24 * 60 * 60,
+    "w" => 7 * 24 * 60 * 60,
+    _ => return Err(format!("unknown unit in duration `{}`", text)),
+  };
+  number
+    .checked_mul(seconds)
+    .map(Duration::from_secs)
+    .ok_or_else(|| format!("duration `{}` is too long", text))
+}
+
+/// Splits `text` into its leading digits and the rest, both trimmed.
+fn split_number(text: &str) -> (&str, &str) {
+  let text = text.trim();
+  let digits = text.bytes().take_while(u8::is_ascii_digit).count();
+  (&text[..digits], text[digits..].trim())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_sizes() {
+    assert_eq!(parse_size("1048576"), Ok(1048576));
+    assert_eq!(parse_size("512KB"), Ok(512 * 1024));
+    assert_eq!(parse_size(" 10 MiB "), Ok(10 * 1024 * 1024));
+    assert_eq!(parse_size("2g"), Ok(2 << 30));
+    assert_eq!(parse_size("1TB"), Ok(1 << 40));
+    assert_eq!(parse_size("0b"), Ok(0));
+  }
+
+  #[test]
+  fn rejects_invalid_sizes() {
+    assert!(parse_size("").is_err());
+    assert!(parse_size("MB").is_err());
+    assert!(parse_size("-1").is_err());
+    assert!(parse_size("1.5MB").is_err());
+    assert!(parse_size("10 PB").is_err());
+    assert!(parse_size(&format!("{}T", u128::MAX)).is_err());
+  }
+
+  #[test]
+  fn parses_durations() {
+    assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+    assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+    assert_eq!(parse_duration("30m"), Ok(Duration::from_secs(30 * 60)));
+    assert_eq!(parse_duration("12 H"), Ok(Duration::from_secs(12 * 60 * 60)));
+    assert_eq!(parse_duration("7d"), Ok(Duration::from_secs(7 * 24 * 60 * 60)));
+    assert_eq!(parse_duration("2w"), Ok(Duration::from_secs(14 * 24 * 60 * 60)));
+  }
+
+  #[test]
+  fn rejects_invalid_durations() {
+    assert!(parse_duration("").is_err());
+    assert!(parse_duration("h").is_err());
+    assert!(parse_duration("1y").is_err());
+    assert!(parse_duration("1.5h").is_err());
+    assert!(parse_duration(&format!("{}w", u64::MAX)).is_err());
+  }
+}